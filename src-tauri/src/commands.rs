@@ -1,14 +1,18 @@
 use serde::{Deserialize, Serialize};
 use base64::Engine;
 use sha2::{Digest, Sha256};
-use std::io::{Read, Write};
+use std::collections::HashMap;
+use std::io::{BufRead, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::sync::{Mutex, OnceLock};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
+use tauri::Emitter;
 use url::Url;
 use walkdir::WalkDir;
 
+use crate::settings::{EngineLimits, GuiSettings};
+
 /// LLM API response with token usage information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmCompletionResponse {
@@ -17,6 +21,8 @@ pub struct LlmCompletionResponse {
     pub input_tokens: u64,
     pub output_tokens: u64,
     pub cached_input_tokens: u64,
+    #[serde(default)]
+    pub web_search_calls: u64,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -29,11 +35,25 @@ pub struct GitHubCliStatusResponse {
 
 #[derive(Debug, Clone, Serialize)]
 pub struct EngineStartResponse {
+    pub engine_id: String,
     pub url: String,
     pub token: String,
     pub default_project_path: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineInfo {
+    pub id: String,
+    pub project_path: String,
+    pub url: String,
+    pub port: u16,
+    pub pid: Option<u32>,
+    pub env_file: Option<String>,
+    pub started_at: i64,
+    pub running: bool,
+}
+
 fn run_cmd_capture(cmd: &str, args: &[&str], cwd: Option<&str>) -> Result<(i32, String), String> {
     let mut c = std::process::Command::new(cmd);
     c.args(args);
@@ -117,14 +137,309 @@ pub fn github_cli_status(cwd: Option<String>) -> Result<GitHubCliStatusResponse,
 
 #[derive(Debug)]
 struct EngineState {
+    id: String,
+    project_path: String,
     url: String,
+    port: u16,
     token: String,
+    env_file: Option<String>,
+    /// CLI binary the daemon was started from, so pruning can leave its version alone.
+    cli_bin: String,
+    started_at: i64,
     child: Option<std::process::Child>,
+    rpc: Option<Arc<DaemonRpcClient>>,
 }
 
-fn engine_state() -> &'static Mutex<Option<EngineState>> {
-    static STATE: OnceLock<Mutex<Option<EngineState>>> = OnceLock::new();
-    STATE.get_or_init(|| Mutex::new(None))
+impl EngineState {
+    fn is_running(&mut self) -> bool {
+        match self.child.as_mut() {
+            Some(child) => child.try_wait().ok().flatten().is_none(),
+            None => true,
+        }
+    }
+
+    fn info(&mut self) -> EngineInfo {
+        EngineInfo {
+            id: self.id.clone(),
+            project_path: self.project_path.clone(),
+            url: self.url.clone(),
+            port: self.port,
+            pid: self.child.as_ref().map(|c| c.id()),
+            env_file: self.env_file.clone(),
+            started_at: self.started_at,
+            running: self.is_running(),
+        }
+    }
+
+    fn start_response(&self) -> EngineStartResponse {
+        EngineStartResponse {
+            engine_id: self.id.clone(),
+            url: self.url.clone(),
+            token: self.token.clone(),
+            default_project_path: self.project_path.clone(),
+        }
+    }
+
+    fn has_exited(&mut self) -> bool {
+        match self.child.as_mut() {
+            Some(child) => child.try_wait().ok().flatten().is_some(),
+            None => true,
+        }
+    }
+
+    /// Ask the daemon to exit on its own so it can flush `sessions.db`. Does not wait.
+    /// Tries the `shutdown` RPC first and falls back to SIGTERM.
+    fn request_shutdown(&mut self) {
+        if self.has_exited() {
+            return;
+        }
+        let params = serde_json::json!({ "token": self.token });
+        let rpc = match self.rpc.take() {
+            Some(client) if !client.is_closed() => {
+                let res = client.request("shutdown", params, Duration::from_millis(800));
+                client.close();
+                res
+            }
+            _ => daemon_rpc_call_once(self.port, "shutdown", params, Duration::from_millis(800)),
+        };
+        if rpc.is_err() {
+            if let Some(child) = self.child.as_ref() {
+                terminate_signal(child.id());
+            }
+        }
+    }
+
+    fn kill(&mut self) {
+        if let Some(rpc) = self.rpc.take() {
+            rpc.close();
+        }
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+            daemon_pidfile_remove(child.id());
+        }
+    }
+}
+
+const DAEMON_SHUTDOWN_GRACE: Duration = Duration::from_secs(3);
+
+/// Graceful shutdown for a batch of engines: signal all of them, wait up to
+/// `DAEMON_SHUTDOWN_GRACE` for them to exit, then kill whatever is left.
+fn shutdown_engines(mut states: Vec<EngineState>) {
+    for st in states.iter_mut() {
+        st.request_shutdown();
+    }
+    let deadline = std::time::Instant::now() + DAEMON_SHUTDOWN_GRACE;
+    while std::time::Instant::now() < deadline && states.iter_mut().any(|st| !st.has_exited()) {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    for st in states.iter_mut() {
+        st.kill();
+    }
+}
+
+#[cfg(unix)]
+fn terminate_signal(pid: u32) {
+    // SAFETY: kill(2) has no memory-safety preconditions.
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGTERM);
+    }
+}
+
+#[cfg(not(unix))]
+fn terminate_signal(_pid: u32) {}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks for existence/permission.
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+/// Windows has neither `kill(2)` nor `/proc`; look the one process up through sysinfo.
+#[cfg(not(unix))]
+fn with_sysinfo_process<R>(pid: u32, f: impl FnOnce(&sysinfo::Process) -> R) -> Option<R> {
+    let pid = sysinfo::Pid::from_u32(pid);
+    let mut system = sysinfo::System::new();
+    system.refresh_processes_specifics(
+        sysinfo::ProcessesToUpdate::Some(&[pid]),
+        true,
+        sysinfo::ProcessRefreshKind::nothing().with_cmd(sysinfo::UpdateKind::Always),
+    );
+    system.process(pid).map(f)
+}
+
+#[cfg(not(unix))]
+fn process_alive(pid: u32) -> bool {
+    with_sysinfo_process(pid, |_| ()).is_some()
+}
+
+#[cfg(unix)]
+fn force_kill_pid(pid: u32) {
+    // SAFETY: kill(2) has no memory-safety preconditions.
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn force_kill_pid(pid: u32) {
+    with_sysinfo_process(pid, |p| p.kill());
+}
+
+#[cfg(target_os = "linux")]
+fn process_command_line(pid: u32) -> Option<String> {
+    let raw = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let parts: Vec<String> = raw
+        .split(|b| *b == 0)
+        .filter(|p| !p.is_empty())
+        .map(|p| String::from_utf8_lossy(p).to_string())
+        .collect();
+    Some(parts.join(" "))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_command_line(pid: u32) -> Option<String> {
+    let (code, text) = run_cmd_capture("ps", &["-o", "command=", "-p", &pid.to_string()], None).ok()?;
+    if code == 0 && !text.is_empty() {
+        Some(text)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn process_command_line(pid: u32) -> Option<String> {
+    with_sysinfo_process(pid, |p| {
+        p.cmd()
+            .iter()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    })
+    .filter(|cmd| !cmd.is_empty())
+}
+
+/// One spawned daemon, recorded in `~/.snailer/daemons.pid.json` so a later launch can
+/// reap daemons orphaned by a crash or force quit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DaemonPidRecord {
+    engine_id: String,
+    pid: u32,
+    port: u16,
+    gui_pid: u32,
+    started_at: i64,
+}
+
+fn daemon_pidfile_path() -> PathBuf {
+    snailer_home_dir().join("daemons.pid.json")
+}
+
+fn daemon_pidfile_lock() -> &'static Mutex<()> {
+    static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+    LOCK.get_or_init(|| Mutex::new(()))
+}
+
+fn read_daemon_pidfile() -> Vec<DaemonPidRecord> {
+    crate::persist::read_json(&daemon_pidfile_path()).unwrap_or_default()
+}
+
+fn write_daemon_pidfile(records: &[DaemonPidRecord]) {
+    if let Ok(text) = serde_json::to_string_pretty(records) {
+        let _ = crate::persist::write(&daemon_pidfile_path(), &text, crate::persist::Policy::JSON);
+    }
+}
+
+fn daemon_pidfile_add(record: DaemonPidRecord) {
+    let _guard = daemon_pidfile_lock().lock();
+    let mut records = read_daemon_pidfile();
+    records.retain(|r| r.pid != record.pid);
+    records.push(record);
+    write_daemon_pidfile(&records);
+}
+
+fn daemon_pidfile_remove(pid: u32) {
+    let _guard = daemon_pidfile_lock().lock();
+    let mut records = read_daemon_pidfile();
+    let before = records.len();
+    records.retain(|r| r.pid != pid);
+    if records.len() != before {
+        write_daemon_pidfile(&records);
+    }
+}
+
+/// Guard against pid reuse: only signal processes that still look like the daemon we spawned.
+fn looks_like_snailer_daemon(cmdline: &str, port: u16) -> bool {
+    let port = port.to_string();
+    let args: Vec<&str> = cmdline.split_whitespace().collect();
+    args.contains(&"daemon") && args.windows(2).any(|w| w[0] == "--port" && w[1] == port)
+}
+
+/// Terminate daemons left behind by a previous GUI process that is no longer running.
+pub fn reap_orphan_daemons() {
+    let me = std::process::id();
+    // Only snapshot under the lock: `engine_start` records new daemons while holding
+    // `engines()`, so it must not wait out the grace period below.
+    let orphans: Vec<DaemonPidRecord> = {
+        let _guard = daemon_pidfile_lock().lock();
+        read_daemon_pidfile()
+            .into_iter()
+            .filter(|r| r.gui_pid != me && !process_alive(r.gui_pid))
+            .collect()
+    };
+    if orphans.is_empty() {
+        return;
+    }
+
+    let targets: Vec<u32> = orphans
+        .iter()
+        .filter(|r| process_alive(r.pid))
+        .filter(|r| {
+            process_command_line(r.pid)
+                .map(|cmd| looks_like_snailer_daemon(&cmd, r.port))
+                .unwrap_or(false)
+        })
+        .map(|r| r.pid)
+        .collect();
+    // Windows has no soft signal to wait on; go straight to the hard kill there.
+    if cfg!(unix) && !targets.is_empty() {
+        for pid in &targets {
+            terminate_signal(*pid);
+        }
+        let deadline = std::time::Instant::now() + DAEMON_SHUTDOWN_GRACE;
+        while std::time::Instant::now() < deadline && targets.iter().any(|pid| process_alive(*pid)) {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+    for pid in targets {
+        if process_alive(pid) {
+            force_kill_pid(pid);
+        }
+    }
+
+    // Re-read so records added while reaping are kept.
+    let _guard = daemon_pidfile_lock().lock();
+    let mut records = read_daemon_pidfile();
+    records.retain(|r| !orphans.iter().any(|o| o.pid == r.pid && o.gui_pid == r.gui_pid));
+    write_daemon_pidfile(&records);
+}
+
+const DEFAULT_ENGINE_ID: &str = "default";
+
+/// Running daemons keyed by engine id (a workspace path or a caller-chosen id).
+fn engines() -> &'static Mutex<HashMap<String, EngineState>> {
+    static STATE: OnceLock<Mutex<HashMap<String, EngineState>>> = OnceLock::new();
+    STATE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn resolve_engine_id(engine_id: Option<&str>, project: Option<&Path>) -> String {
+    if let Some(id) = engine_id.map(str::trim).filter(|id| !id.is_empty()) {
+        return id.to_string();
+    }
+    match project {
+        Some(p) => p.to_string_lossy().to_string(),
+        None => DEFAULT_ENGINE_ID.to_string(),
+    }
 }
 
 fn find_free_port() -> std::io::Result<u16> {
@@ -132,16 +447,272 @@ fn find_free_port() -> std::io::Result<u16> {
     Ok(listener.local_addr()?.port())
 }
 
-fn wait_for_port(port: u16, timeout: Duration) -> Result<(), String> {
+const DEFAULT_DAEMON_STARTUP_TIMEOUT_SECS: u64 = 30;
+const DAEMON_PORT_RACE_ATTEMPTS: usize = 3;
+
+/// Startup timeout: explicit value, else `SNAILER_DAEMON_STARTUP_TIMEOUT_SECS`, else 30s.
+/// A cold npm cache can make the first start slow, so this is deliberately generous.
+fn daemon_startup_timeout(explicit_secs: Option<u64>) -> Duration {
+    let secs = explicit_secs
+        .filter(|s| *s > 0)
+        .or_else(|| {
+            std::env::var("SNAILER_DAEMON_STARTUP_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .filter(|s| *s > 0)
+        })
+        .unwrap_or(DEFAULT_DAEMON_STARTUP_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// True when daemon output says its port was taken between `find_free_port` and bind.
+fn is_port_conflict_output(lines: &[String]) -> bool {
+    lines.iter().any(|l| {
+        let l = l.to_ascii_lowercase();
+        l.contains("eaddrinuse") || l.contains("address already in use")
+    })
+}
+
+enum DaemonReadyError {
+    /// The daemon process exited before answering `initialize`.
+    Exited(std::process::ExitStatus),
+    /// No authenticated `initialize` round-trip within the timeout.
+    TimedOut(String),
+    /// The daemon answered but is not compatible with this GUI.
+    Incompatible(String),
+}
+
+/// Wait until the daemon answers an authenticated `initialize` with our token.
+///
+/// A bare TCP accept is not enough: another process may have grabbed the port, and only
+/// our daemon knows the generated token.
+fn wait_for_daemon_ready(
+    child: &mut std::process::Child,
+    port: u16,
+    token: &str,
+    project_path: &str,
+    timeout: Duration,
+) -> Result<(), DaemonReadyError> {
     let start = std::time::Instant::now();
-    let addr = format!("127.0.0.1:{port}");
-    while start.elapsed() < timeout {
-        if std::net::TcpStream::connect(&addr).is_ok() {
-            return Ok(());
+    let params = serde_json::json!({ "token": token, "projectPath": project_path });
+    let mut last_error = "daemon never accepted a connection".to_string();
+    loop {
+        if let Ok(Some(status)) = child.try_wait() {
+            return Err(DaemonReadyError::Exited(status));
         }
-        std::thread::sleep(Duration::from_millis(50));
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return Err(DaemonReadyError::TimedOut(format!(
+                "Timed out after {:?} waiting for Snailer daemon on port {} to answer initialize (last error: {})",
+                timeout, port, last_error
+            )));
+        }
+        let attempt_timeout = remaining.min(Duration::from_secs(2));
+        match daemon_rpc_call_once(port, "initialize", params.clone(), attempt_timeout) {
+            Ok(result) => {
+                return check_daemon_capabilities(&result).map_err(DaemonReadyError::Incompatible);
+            }
+            Err(e) => last_error = e,
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+const DAEMON_LOG_MAX_BYTES: u64 = 5 * 1024 * 1024;
+const DAEMON_LOG_KEEP_FILES: usize = 5;
+const DAEMON_LOG_RECENT_LINES: usize = 200;
+
+/// A single line of daemon output, as persisted under `~/.snailer/logs` and emitted on `engine://log`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineLogLine {
+    pub engine_id: String,
+    pub ts: i64, // unix millis
+    pub stream: String, // "stdout" | "stderr" | "gui"
+    pub line: String,
+}
+
+/// Size-rotated sink for one engine's output: `<stem>-0.log` is the active file, older
+/// files shift up to `<stem>-4.log` and the oldest is dropped.
+struct DaemonLogSink {
+    dir: PathBuf,
+    stem: String,
+    engine_id: String,
+    file: Option<std::fs::File>,
+    size: u64,
+    recent: std::collections::VecDeque<EngineLogLine>,
+    /// Lines appended since open; a mark for "output after this point".
+    appended: u64,
+}
+
+/// File stem for an engine's logs. The default engine keeps the plain `daemon-N.log` names;
+/// other engines get a readable slug plus a short hash of their id.
+fn daemon_log_stem(engine_id: &str) -> String {
+    if engine_id == DEFAULT_ENGINE_ID {
+        return "daemon".to_string();
+    }
+    let name = Path::new(engine_id)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let slug: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .take(32)
+        .collect();
+    let digest = Sha256::digest(engine_id.as_bytes());
+    let hash: String = digest.iter().take(4).map(|b| format!("{:02x}", b)).collect();
+    if slug.is_empty() {
+        format!("daemon-{}", hash)
+    } else {
+        format!("daemon-{}-{}", slug, hash)
+    }
+}
+
+fn daemon_log_path(dir: &Path, stem: &str, index: usize) -> PathBuf {
+    dir.join(format!("{}-{}.log", stem, index))
+}
+
+fn open_append(path: &Path) -> std::io::Result<std::fs::File> {
+    std::fs::OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl DaemonLogSink {
+    fn open(dir: PathBuf, engine_id: &str) -> Result<Self, String> {
+        std::fs::create_dir_all(&dir).map_err(|e| format!("mkdir failed: {}", e))?;
+        let stem = daemon_log_stem(engine_id);
+        let file = open_append(&daemon_log_path(&dir, &stem, 0)).map_err(|e| format!("open log failed: {}", e))?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            dir,
+            stem,
+            engine_id: engine_id.to_string(),
+            file: Some(file),
+            size,
+            recent: std::collections::VecDeque::new(),
+            appended: 0,
+        })
+    }
+
+    fn rotate(&mut self) {
+        self.file = None;
+        let _ = std::fs::remove_file(daemon_log_path(&self.dir, &self.stem, DAEMON_LOG_KEEP_FILES - 1));
+        for i in (0..DAEMON_LOG_KEEP_FILES - 1).rev() {
+            let from = daemon_log_path(&self.dir, &self.stem, i);
+            if from.exists() {
+                let _ = std::fs::rename(&from, daemon_log_path(&self.dir, &self.stem, i + 1));
+            }
+        }
+        self.file = open_append(&daemon_log_path(&self.dir, &self.stem, 0)).ok();
+        self.size = 0;
+    }
+
+    fn append(&mut self, entry: &EngineLogLine) {
+        let text = format_daemon_log_line(entry);
+        if self.size > 0 && self.size + text.len() as u64 > DAEMON_LOG_MAX_BYTES {
+            self.rotate();
+        }
+        if let Some(f) = self.file.as_mut() {
+            if f.write_all(text.as_bytes()).is_ok() {
+                self.size += text.len() as u64;
+            }
+        }
+        if self.recent.len() >= DAEMON_LOG_RECENT_LINES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(entry.clone());
+        self.appended += 1;
+    }
+
+    /// The last `max_lines` of daemon output appended after mark `since` (see `appended`).
+    fn recent_output(&self, since: u64, max_lines: usize) -> Vec<String> {
+        let new_lines = self.appended.saturating_sub(since).min(self.recent.len() as u64) as usize;
+        let lines: Vec<String> = self
+            .recent
+            .iter()
+            .skip(self.recent.len() - new_lines)
+            .filter(|e| e.stream != "gui")
+            .map(|e| e.line.clone())
+            .collect();
+        lines[lines.len().saturating_sub(max_lines)..].to_vec()
+    }
+}
+
+fn format_daemon_log_line(entry: &EngineLogLine) -> String {
+    let ts = chrono::DateTime::<chrono::Utc>::from_timestamp_millis(entry.ts)
+        .unwrap_or_else(chrono::Utc::now)
+        .to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    format!("{} [{}] {}\n", ts, entry.stream, entry.line)
+}
+
+fn parse_daemon_log_line(engine_id: &str, raw: &str) -> Option<EngineLogLine> {
+    let (ts, rest) = raw.split_once(' ')?;
+    let ts = chrono::DateTime::parse_from_rfc3339(ts).ok()?.timestamp_millis();
+    let rest = rest.strip_prefix('[')?;
+    let (stream, line) = rest.split_once(']')?;
+    Some(EngineLogLine {
+        engine_id: engine_id.to_string(),
+        ts,
+        stream: stream.to_string(),
+        line: line.strip_prefix(' ').unwrap_or(line).to_string(),
+    })
+}
+
+fn read_daemon_logs(dir: &Path, engine_id: &str, tail_lines: usize, since: Option<i64>) -> Vec<EngineLogLine> {
+    let stem = daemon_log_stem(engine_id);
+    let mut out = Vec::new();
+    // Oldest rotated file first so the result is chronological.
+    for i in (0..DAEMON_LOG_KEEP_FILES).rev() {
+        let Ok(text) = std::fs::read_to_string(daemon_log_path(dir, &stem, i)) else {
+            continue;
+        };
+        out.extend(
+            text.lines()
+                .filter_map(|raw| parse_daemon_log_line(engine_id, raw))
+                .filter(|e| since.map(|s| e.ts >= s).unwrap_or(true)),
+        );
     }
-    Err(format!("Timed out waiting for Snailer daemon to listen on {addr}"))
+    let skip = out.len().saturating_sub(tail_lines);
+    out.split_off(skip)
+}
+
+fn record_daemon_log(sink: &Mutex<DaemonLogSink>, app: &tauri::AppHandle, stream: &str, line: String) {
+    let Ok(mut guard) = sink.lock() else {
+        return;
+    };
+    let entry = EngineLogLine {
+        engine_id: guard.engine_id.clone(),
+        ts: chrono::Utc::now().timestamp_millis(),
+        stream: stream.to_string(),
+        line,
+    };
+    guard.append(&entry);
+    drop(guard);
+    let _ = app.emit("engine://log", entry);
+}
+
+fn pipe_daemon_output<R: Read + Send + 'static>(
+    reader: R,
+    stream: &'static str,
+    sink: Arc<Mutex<DaemonLogSink>>,
+    app: tauri::AppHandle,
+) {
+    std::thread::spawn(move || {
+        let mut reader = std::io::BufReader::new(reader);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&buf)
+                        .trim_end_matches(['\r', '\n'])
+                        .to_string();
+                    record_daemon_log(&sink, &app, stream, line);
+                }
+            }
+        }
+    });
 }
 
 fn default_project_path() -> PathBuf {
@@ -173,7 +744,7 @@ fn home_dir() -> PathBuf {
     std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
 }
 
-fn snailer_home_dir() -> PathBuf {
+pub(crate) fn snailer_home_dir() -> PathBuf {
     home_dir().join(".snailer")
 }
 
@@ -181,6 +752,10 @@ fn snailer_attachments_dir() -> PathBuf {
     snailer_home_dir().join("gui_attachments")
 }
 
+fn snailer_logs_dir() -> PathBuf {
+    snailer_home_dir().join("logs")
+}
+
 fn snailer_cli_prefix_dir() -> PathBuf {
     snailer_home_dir().join("npm_cli")
 }
@@ -249,105 +824,472 @@ fn detect_latest_lts_node_version() -> Option<String> {
     None
 }
 
-fn install_node_if_needed() -> Result<PathBuf, String> {
-    let bin = snailer_node_current_bin_dir();
-    let npm = if cfg!(target_os = "windows") {
-        bin.join("npm.cmd")
-    } else {
-        bin.join("npm")
-    };
-    let node = if cfg!(target_os = "windows") {
-        bin.join("node.exe")
-    } else {
-        bin.join("node")
-    };
-    if npm.is_file() && node.is_file() {
-        return Ok(bin);
-    }
-
-    let version = detect_latest_lts_node_version().unwrap_or_else(|| "v20.11.1".to_string());
-    let platform = node_platform_string()?;
+/// Payload for `install://progress`.
+///
+/// `phase` is one of `node-download`, `node-verify`, `node-extract`, `npm-install`,
+/// `health-check`, then a final `done` or `error`.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallProgress {
+    pub phase: String,
+    pub message: Option<String>,
+    pub bytes: Option<u64>,
+    pub total: Option<u64>,
+    /// One line of npm output during `npm-install`.
+    pub line: Option<String>,
+}
 
-    let root = snailer_node_root_dir();
-    std::fs::create_dir_all(&root).map_err(|e| format!("mkdir failed: {}", e))?;
-    let cache_dir = root.join("cache");
-    std::fs::create_dir_all(&cache_dir).map_err(|e| format!("mkdir failed: {}", e))?;
+/// Emits `install://progress` when an app handle is available; a no-op otherwise.
+#[derive(Clone, Default)]
+struct InstallReporter {
+    app: Option<tauri::AppHandle>,
+}
 
-    let file_name = format!("node-{}-{}.tar.gz", version, platform);
-    let url = format!("https://nodejs.org/dist/{}/{}", version, file_name);
-    let archive_path = cache_dir.join(&file_name);
+impl InstallReporter {
+    fn new(app: tauri::AppHandle) -> Self {
+        Self { app: Some(app) }
+    }
 
-    // Download
-    let resp = ureq::get(&url)
-        .call()
-        .map_err(|e| format!("Failed to download Node.js: {e}"))?;
-    let mut reader = resp.into_reader();
-    let mut out = std::fs::File::create(&archive_path).map_err(|e| format!("write failed: {e}"))?;
-    std::io::copy(&mut reader, &mut out).map_err(|e| format!("download write failed: {e}"))?;
+    fn emit(&self, progress: InstallProgress) {
+        if let Some(app) = self.app.as_ref() {
+            let _ = app.emit("install://progress", progress);
+        }
+    }
 
-    // Extract to a temp dir, then move into `current`
-    let tmp = root.join(format!("tmp-{}", uuid::Uuid::new_v4()));
-    std::fs::create_dir_all(&tmp).map_err(|e| format!("mkdir failed: {}", e))?;
-    let status = std::process::Command::new("tar")
-        .arg("-xzf")
-        .arg(&archive_path)
-        .arg("-C")
-        .arg(&tmp)
-        .status()
-        .map_err(|e| format!("Failed to run tar: {e}"))?;
-    if !status.success() {
-        return Err("Failed to extract Node.js archive (tar)".to_string());
+    fn phase(&self, phase: &str, message: impl Into<String>) {
+        self.emit(InstallProgress {
+            phase: phase.to_string(),
+            message: Some(message.into()),
+            ..Default::default()
+        });
     }
 
-    let extracted = tmp.join(format!("node-{}-{}", version, platform));
-    if !extracted.is_dir() {
-        return Err("Node.js archive extracted but expected directory was not found.".to_string());
+    fn bytes(&self, phase: &str, bytes: u64, total: Option<u64>) {
+        self.emit(InstallProgress {
+            phase: phase.to_string(),
+            bytes: Some(bytes),
+            total,
+            ..Default::default()
+        });
     }
 
-    let current = snailer_node_current_dir();
-    if current.exists() {
-        let _ = std::fs::remove_dir_all(&current);
+    fn line(&self, phase: &str, line: String) {
+        self.emit(InstallProgress {
+            phase: phase.to_string(),
+            line: Some(line),
+            ..Default::default()
+        });
     }
-    std::fs::rename(&extracted, &current).map_err(|e| format!("install failed: {}", e))?;
-    let _ = std::fs::remove_dir_all(&tmp);
 
-    let bin = snailer_node_current_bin_dir();
-    let npm = if cfg!(target_os = "windows") {
-        bin.join("npm.cmd")
-    } else {
-        bin.join("npm")
-    };
-    if !npm.is_file() {
-        return Err("Node.js installed but npm was not found.".to_string());
+    /// Final event for an install flow.
+    fn finish<T>(&self, result: &Result<T, String>, success: &str) {
+        match result {
+            Ok(_) => self.phase("done", success),
+            Err(e) => self.phase("error", e.clone()),
+        }
     }
-    Ok(bin)
 }
 
-fn resolve_npm_command() -> Result<(String, Option<PathBuf>), String> {
-    // First try system npm.
-    if let Ok(out) = std::process::Command::new("npm").arg("--version").output() {
-        if out.status.success() {
-            return Ok(("npm".to_string(), None));
+/// Copy a download to `out`, reporting bytes at most every 256 KiB.
+fn copy_with_progress(
+    reader: &mut dyn Read,
+    out: &mut dyn Write,
+    total: Option<u64>,
+    reporter: &InstallReporter,
+    phase: &str,
+) -> std::io::Result<u64> {
+    const REPORT_EVERY: u64 = 256 * 1024;
+    let mut buf = [0u8; 64 * 1024];
+    let mut done: u64 = 0;
+    let mut last_report: u64 = 0;
+    reporter.bytes(phase, 0, total);
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        out.write_all(&buf[..n])?;
+        done += n as u64;
+        if done - last_report >= REPORT_EVERY {
+            reporter.bytes(phase, done, total);
+            last_report = done;
         }
     }
+    reporter.bytes(phase, done, total);
+    Ok(done)
+}
 
-    // Fallback: install bundled Node/npm under ~/.snailer/node/current
-    let bin = install_node_if_needed()?;
-    let npm_path = if cfg!(target_os = "windows") {
-        bin.join("npm.cmd")
-    } else {
-        bin.join("npm")
-    };
-    Ok((npm_path.to_string_lossy().to_string(), Some(bin)))
+/// Local install bundle for machines without network access.
+///
+/// The directory may hold `node-<ver>-<platform>.tar.gz` with its `SHASUMS256.txt`, a packed
+/// CLI (`snailer-team-snailer-<ver>.tgz`, as produced by `npm pack`) and/or an `npm-cache/`
+/// directory populated with `npm cache add`.
+#[derive(Debug, Clone, Default)]
+struct OfflineBundle {
+    dir: PathBuf,
+    node_archive: Option<PathBuf>,
+    cli_tgz: Option<PathBuf>,
+    npm_cache: Option<PathBuf>,
+}
+
+/// Bundle directory: `SNAILER_OFFLINE_BUNDLE`, else `offlineBundleDir` in `gui_settings.json`.
+fn offline_bundle_dir() -> Option<PathBuf> {
+    non_empty_trimmed(std::env::var("SNAILER_OFFLINE_BUNDLE").ok())
+        .or_else(|| gui_settings().offline_bundle_dir)
+        .map(PathBuf::from)
 }
 
-fn snailer_cli_bin_path(prefix: &Path) -> PathBuf {
-    let base = prefix.join("node_modules").join(".bin");
-    #[cfg(target_os = "windows")]
-    {
-        return base.join("snailer.cmd");
-    }
-    #[cfg(not(target_os = "windows"))]
+fn offline_bundle() -> Option<OfflineBundle> {
+    let dir = offline_bundle_dir()?;
+    let platform = node_platform_string().ok()?;
+    let cli_req = snailer_cli_version_req().ok()?;
+    Some(scan_offline_bundle(&dir, platform, &cli_req))
+}
+
+/// With several candidates the highest version wins; CLI packages outside `cli_req` are ignored.
+fn scan_offline_bundle(dir: &Path, platform: &str, cli_req: &semver::VersionReq) -> OfflineBundle {
+    let mut bundle = OfflineBundle {
+        dir: dir.to_path_buf(),
+        ..Default::default()
+    };
+    let names: Vec<String> = std::fs::read_dir(dir)
+        .map(|rd| {
+            rd.filter_map(|e| e.ok())
+                .filter_map(|e| e.file_name().to_str().map(|s| s.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut best_node: Option<semver::Version> = None;
+    let mut best_cli: Option<semver::Version> = None;
+    for name in names {
+        let path = dir.join(&name);
+        if !path.is_file() {
+            if path.is_dir() && name == "npm-cache" {
+                bundle.npm_cache = Some(path);
+            }
+            continue;
+        }
+        if let Some(version) = node_version_from_archive_name(&name, platform)
+            .and_then(|v| semver::Version::parse(v.trim_start_matches('v')).ok())
+        {
+            if best_node.as_ref().map_or(true, |best| version > *best) {
+                best_node = Some(version);
+                bundle.node_archive = Some(path);
+            }
+        } else if let Some(version) = name
+            .strip_prefix("snailer-team-snailer-")
+            .and_then(|rest| rest.strip_suffix(".tgz"))
+            .and_then(|v| semver::Version::parse(v).ok())
+        {
+            if cli_req.matches(&version) && best_cli.as_ref().map_or(true, |best| version > *best) {
+                best_cli = Some(version);
+                bundle.cli_tgz = Some(path);
+            }
+        }
+    }
+    bundle
+}
+
+/// `node-v20.11.1-linux-x64.tar.gz` -> `v20.11.1` when the platform matches.
+fn node_version_from_archive_name(file_name: &str, platform: &str) -> Option<String> {
+    let version = file_name
+        .strip_prefix("node-")?
+        .strip_suffix(".tar.gz")?
+        .strip_suffix(platform)?
+        .strip_suffix('-')?;
+    (version.starts_with('v') && !version.contains('/')).then(|| version.to_string())
+}
+
+/// Checksum verification is mandatory unless `SNAILER_SKIP_NODE_CHECKSUM=1` is set explicitly.
+fn node_checksum_skipped() -> bool {
+    matches!(
+        std::env::var("SNAILER_SKIP_NODE_CHECKSUM").ok().as_deref().map(str::trim),
+        Some("1") | Some("true")
+    )
+}
+
+fn fetch_node_shasums(version: &str) -> Result<String, String> {
+    let url = format!("https://nodejs.org/dist/{}/SHASUMS256.txt", version);
+    let resp = ureq::get(&url)
+        .call()
+        .map_err(|e| format!("Failed to download Node.js checksums: {e}"))?;
+    let mut body = String::new();
+    resp.into_reader()
+        .read_to_string(&mut body)
+        .map_err(|e| format!("Failed to read Node.js checksums: {e}"))?;
+    Ok(body)
+}
+
+/// Find `file_name` in a `SHASUMS256.txt` body (`<hex>  <file>` per line).
+fn expected_sha256_for(shasums: &str, file_name: &str) -> Option<String> {
+    shasums.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let hash = parts.next()?;
+        let name = parts.next()?.trim_start_matches('*');
+        (name == file_name && hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+            .then(|| hash.to_ascii_lowercase())
+    })
+}
+
+fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("open {} failed: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).map_err(|e| format!("read {} failed: {}", path.display(), e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// True if `path` is relative and never climbs above the archive root.
+fn archive_path_is_contained(path: &Path) -> bool {
+    let mut depth: usize = 0;
+    for comp in path.components() {
+        match comp {
+            std::path::Component::Normal(_) => depth += 1,
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                if depth == 0 {
+                    return false;
+                }
+                depth -= 1;
+            }
+            std::path::Component::RootDir | std::path::Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+/// Extract a `.tar.gz` in-process, refusing entries (or link targets) that escape `dest`.
+fn extract_tar_gz(archive_path: &Path, dest: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dest).map_err(|e| format!("mkdir failed: {}", e))?;
+    let file = std::fs::File::open(archive_path).map_err(|e| format!("open archive failed: {}", e))?;
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(std::io::BufReader::new(file)));
+    let entries = archive.entries().map_err(|e| format!("read archive failed: {}", e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("read archive entry failed: {}", e))?;
+        let path = entry
+            .path()
+            .map_err(|e| format!("bad archive path: {}", e))?
+            .into_owned();
+        if !archive_path_is_contained(&path) {
+            return Err(format!("archive entry escapes destination: {}", path.display()));
+        }
+        if let Some(target) = entry.link_name().map_err(|e| format!("bad link target: {}", e))? {
+            let kind = entry.header().entry_type();
+            // Symlink targets resolve from the link's directory; hard links from the archive root.
+            let resolved = if kind.is_symlink() {
+                path.parent().unwrap_or_else(|| Path::new("")).join(&target)
+            } else {
+                target.into_owned()
+            };
+            if !archive_path_is_contained(&resolved) {
+                return Err(format!(
+                    "archive link escapes destination: {} -> {}",
+                    path.display(),
+                    resolved.display()
+                ));
+            }
+        }
+        let unpacked = entry
+            .unpack_in(dest)
+            .map_err(|e| format!("extract {} failed: {}", path.display(), e))?;
+        if !unpacked {
+            return Err(format!("archive entry escapes destination: {}", path.display()));
+        }
+    }
+    Ok(())
+}
+
+fn install_node_if_needed(reporter: &InstallReporter) -> Result<PathBuf, String> {
+    let bin = snailer_node_current_bin_dir();
+    let npm = if cfg!(target_os = "windows") {
+        bin.join("npm.cmd")
+    } else {
+        bin.join("npm")
+    };
+    let node = if cfg!(target_os = "windows") {
+        bin.join("node.exe")
+    } else {
+        bin.join("node")
+    };
+    if npm.is_file() && node.is_file() {
+        return Ok(bin);
+    }
+
+    let platform = node_platform_string()?;
+    let root = snailer_node_root_dir();
+    std::fs::create_dir_all(&root).map_err(|e| format!("mkdir failed: {}", e))?;
+
+    // Air-gapped machines: install from the local bundle without touching the network.
+    if let Some(bundle) = offline_bundle() {
+        if let Some(archive) = bundle.node_archive.as_deref() {
+            return install_node_from_bundle(&bundle, archive, platform, &root, reporter);
+        }
+    }
+
+    let version = detect_latest_lts_node_version().unwrap_or_else(|| "v20.11.1".to_string());
+    let cache_dir = root.join("cache");
+    std::fs::create_dir_all(&cache_dir).map_err(|e| format!("mkdir failed: {}", e))?;
+
+    let file_name = format!("node-{}-{}.tar.gz", version, platform);
+    let url = format!("https://nodejs.org/dist/{}/{}", version, file_name);
+    let archive_path = cache_dir.join(&file_name);
+
+    let expected_sha256 = if node_checksum_skipped() {
+        log::warn!("SNAILER_SKIP_NODE_CHECKSUM is set; installing Node.js {} without verification", version);
+        None
+    } else {
+        let shasums = fetch_node_shasums(&version)?;
+        Some(expected_sha256_for(&shasums, &file_name).ok_or_else(|| {
+            format!("SHASUMS256.txt for Node.js {} has no entry for {}", version, file_name)
+        })?)
+    };
+
+    // Reuse a cached archive only if it still matches the published checksum.
+    let cached_ok = match expected_sha256.as_deref() {
+        Some(expected) => archive_path.is_file() && sha256_file(&archive_path).ok().as_deref() == Some(expected),
+        None => false,
+    };
+    if !cached_ok {
+        reporter.phase("node-download", format!("Downloading Node.js {}", version));
+        let resp = ureq::get(&url)
+            .call()
+            .map_err(|e| format!("Failed to download Node.js: {e}"))?;
+        let total = resp.header("Content-Length").and_then(|v| v.trim().parse::<u64>().ok());
+        let mut reader = resp.into_reader();
+        let mut out = std::fs::File::create(&archive_path).map_err(|e| format!("write failed: {e}"))?;
+        copy_with_progress(&mut reader, &mut out, total, reporter, "node-download")
+            .map_err(|e| format!("download write failed: {e}"))?;
+    }
+
+    if let Some(expected) = expected_sha256.as_deref() {
+        reporter.phase("node-verify", format!("Verifying {}", file_name));
+        let actual = sha256_file(&archive_path)?;
+        if actual != expected {
+            let _ = std::fs::remove_file(&archive_path);
+            return Err(format!(
+                "Node.js archive checksum mismatch for {} (expected {}, got {}). The download was deleted.",
+                file_name, expected, actual
+            ));
+        }
+    }
+
+    install_node_archive(&archive_path, &version, platform, &root, reporter)
+}
+
+/// Install Node from the offline bundle, verified against the bundle's `SHASUMS256.txt`.
+fn install_node_from_bundle(
+    bundle: &OfflineBundle,
+    archive: &Path,
+    platform: &str,
+    root: &Path,
+    reporter: &InstallReporter,
+) -> Result<PathBuf, String> {
+    let file_name = archive
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| format!("invalid Node.js archive path: {}", archive.display()))?;
+    let version = node_version_from_archive_name(file_name, platform)
+        .ok_or_else(|| format!("{} is not a Node.js archive for {}", file_name, platform))?;
+
+    if node_checksum_skipped() {
+        log::warn!("SNAILER_SKIP_NODE_CHECKSUM is set; installing bundled {} without verification", file_name);
+    } else {
+        reporter.phase("node-verify", format!("Verifying bundled {}", file_name));
+        let shasums_path = bundle.dir.join("SHASUMS256.txt");
+        let shasums = std::fs::read_to_string(&shasums_path).map_err(|e| {
+            format!(
+                "Offline bundle needs SHASUMS256.txt next to {} ({}: {})",
+                file_name,
+                shasums_path.display(),
+                e
+            )
+        })?;
+        let expected = expected_sha256_for(&shasums, file_name)
+            .ok_or_else(|| format!("{} has no entry for {}", shasums_path.display(), file_name))?;
+        let actual = sha256_file(archive)?;
+        if actual != expected {
+            // Never delete the user's bundle; only downloads we own are removed on mismatch.
+            return Err(format!(
+                "Bundled Node.js archive checksum mismatch for {} (expected {}, got {}).",
+                file_name, expected, actual
+            ));
+        }
+    }
+    install_node_archive(archive, &version, platform, root, reporter)
+}
+
+/// Extract a verified `node-<version>-<platform>.tar.gz` and swap it into `current`.
+fn install_node_archive(
+    archive_path: &Path,
+    version: &str,
+    platform: &str,
+    root: &Path,
+    reporter: &InstallReporter,
+) -> Result<PathBuf, String> {
+    reporter.phase("node-extract", format!("Extracting Node.js {}", version));
+    // Extract to a temp dir, then move into `current`
+    let tmp = root.join(format!("tmp-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&tmp).map_err(|e| format!("mkdir failed: {}", e))?;
+    if let Err(e) = extract_tar_gz(archive_path, &tmp) {
+        let _ = std::fs::remove_dir_all(&tmp);
+        return Err(format!("Failed to extract Node.js archive: {}", e));
+    }
+
+    let extracted = tmp.join(format!("node-{}-{}", version, platform));
+    if !extracted.is_dir() {
+        return Err("Node.js archive extracted but expected directory was not found.".to_string());
+    }
+
+    let current = snailer_node_current_dir();
+    if current.exists() {
+        let _ = std::fs::remove_dir_all(&current);
+    }
+    std::fs::rename(&extracted, &current).map_err(|e| format!("install failed: {}", e))?;
+    let _ = std::fs::remove_dir_all(&tmp);
+
+    let bin = snailer_node_current_bin_dir();
+    let npm = if cfg!(target_os = "windows") {
+        bin.join("npm.cmd")
+    } else {
+        bin.join("npm")
+    };
+    if !npm.is_file() {
+        return Err("Node.js installed but npm was not found.".to_string());
+    }
+    Ok(bin)
+}
+
+fn resolve_npm_command(reporter: &InstallReporter) -> Result<(String, Option<PathBuf>), String> {
+    // First try system npm.
+    if let Ok(out) = std::process::Command::new("npm").arg("--version").output() {
+        if out.status.success() {
+            return Ok(("npm".to_string(), None));
+        }
+    }
+
+    // Fallback: install bundled Node/npm under ~/.snailer/node/current
+    let bin = install_node_if_needed(reporter)?;
+    let npm_path = if cfg!(target_os = "windows") {
+        bin.join("npm.cmd")
+    } else {
+        bin.join("npm")
+    };
+    Ok((npm_path.to_string_lossy().to_string(), Some(bin)))
+}
+
+fn snailer_cli_bin_path(prefix: &Path) -> PathBuf {
+    let base = prefix.join("node_modules").join(".bin");
+    #[cfg(target_os = "windows")]
+    {
+        return base.join("snailer.cmd");
+    }
+    #[cfg(not(target_os = "windows"))]
     {
         return base.join("snailer");
     }
@@ -382,148 +1324,1215 @@ fn run_quick_cmd(program: &Path, args: &[&str], extra_path: Option<&Path>) -> Re
                     let _ = child.wait();
                     return Ok(false);
                 }
-                std::thread::sleep(Duration::from_millis(40));
+                std::thread::sleep(Duration::from_millis(40));
+            }
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("Failed while waiting {:?}: {}", program, e));
+            }
+        }
+    }
+}
+
+fn run_quick_cmd_stdout(program: &Path, args: &[&str], extra_path: Option<&Path>) -> Result<Option<String>, String> {
+    let mut cmd = std::process::Command::new(program);
+    cmd.args(args).stdout(Stdio::piped()).stderr(Stdio::null());
+    if let Some(bin) = extra_path {
+        cmd.env("PATH", prepend_path(bin));
+    }
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to start {:?} {:?}: {}", program, args, e))?;
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(4);
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                if !status.success() {
+                    return Ok(None);
+                }
+                let mut text = String::new();
+                if let Some(mut out) = child.stdout.take() {
+                    let _ = out.read_to_string(&mut text);
+                }
+                return Ok(Some(text));
+            }
+            Ok(None) => {
+                if std::time::Instant::now() >= deadline {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Ok(None);
+                }
+                std::thread::sleep(Duration::from_millis(40));
+            }
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("Failed while waiting {:?}: {}", program, e));
+            }
+        }
+    }
+}
+
+/// Semver range of `@snailer-team/snailer` this GUI build speaks to.
+/// Overridable at build time (and at runtime for emergencies) via `SNAILER_CLI_VERSION_REQ`.
+const DEFAULT_SNAILER_CLI_VERSION_REQ: &str = "^0.3";
+
+fn snailer_cli_version_req_text() -> String {
+    if let Ok(v) = std::env::var("SNAILER_CLI_VERSION_REQ") {
+        if !v.trim().is_empty() {
+            return v.trim().to_string();
+        }
+    }
+    option_env!("SNAILER_CLI_VERSION_REQ")
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .unwrap_or(DEFAULT_SNAILER_CLI_VERSION_REQ)
+        .to_string()
+}
+
+fn snailer_cli_version_req() -> Result<semver::VersionReq, String> {
+    let text = snailer_cli_version_req_text();
+    semver::VersionReq::parse(&text).map_err(|e| format!("Invalid Snailer CLI version requirement '{}': {}", text, e))
+}
+
+/// npm wants space-separated comparators where the semver crate uses commas.
+fn npm_version_spec(req: &str) -> String {
+    req.split(',').map(str::trim).filter(|p| !p.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+fn parse_cli_version(output: &str) -> Option<semver::Version> {
+    output
+        .split_whitespace()
+        .map(|tok| tok.trim_start_matches('v').trim_end_matches(','))
+        .find_map(|tok| semver::Version::parse(tok).ok())
+}
+
+/// Which way the engine has to move to satisfy `req`.
+fn cli_version_action(installed: &semver::Version, req: &semver::VersionReq) -> &'static str {
+    for c in &req.comparators {
+        if c.matches(installed) {
+            continue;
+        }
+        let base = semver::Version::new(c.major, c.minor.unwrap_or(0), c.patch.unwrap_or(0));
+        if *installed < base {
+            return "upgrade";
+        }
+    }
+    "downgrade"
+}
+
+#[derive(Debug, Clone)]
+enum CliHealth {
+    Broken,
+    Incompatible(semver::Version),
+    Ok(semver::Version),
+}
+
+fn snailer_cli_check(bin_path: &Path, extra_path: Option<&Path>) -> Result<CliHealth, String> {
+    let Some(out) = run_quick_cmd_stdout(bin_path, &["--version"], extra_path)? else {
+        return Ok(CliHealth::Broken);
+    };
+    if !run_quick_cmd(bin_path, &["daemon", "--help"], extra_path)? {
+        return Ok(CliHealth::Broken);
+    }
+    let Some(version) = parse_cli_version(&out) else {
+        return Ok(CliHealth::Broken);
+    };
+    if snailer_cli_version_req()?.matches(&version) {
+        Ok(CliHealth::Ok(version))
+    } else {
+        Ok(CliHealth::Incompatible(version))
+    }
+}
+
+fn snailer_cli_health_ok(bin_path: &Path, extra_path: Option<&Path>) -> Result<bool, String> {
+    Ok(matches!(snailer_cli_check(bin_path, extra_path)?, CliHealth::Ok(_)))
+}
+
+/// Result of comparing the installed Snailer CLI against what this GUI build speaks.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnailerCliCompat {
+    pub required: String,
+    pub installed: Option<String>,
+    pub compatible: bool,
+    pub action: Option<String>, // "upgrade" | "downgrade" | "install"
+}
+
+impl SnailerCliCompat {
+    fn from_health(health: &CliHealth) -> Self {
+        let required = snailer_cli_version_req_text();
+        match health {
+            CliHealth::Ok(v) => Self {
+                required,
+                installed: Some(v.to_string()),
+                compatible: true,
+                action: None,
+            },
+            CliHealth::Incompatible(v) => Self {
+                action: snailer_cli_version_req()
+                    .ok()
+                    .map(|req| cli_version_action(v, &req).to_string()),
+                required,
+                installed: Some(v.to_string()),
+                compatible: false,
+            },
+            CliHealth::Broken => Self {
+                required,
+                installed: None,
+                compatible: false,
+                action: Some("install".to_string()),
+            },
+        }
+    }
+
+    /// Error string for commands; prefixed so the UI can offer the upgrade/downgrade action.
+    fn error_message(&self) -> String {
+        format!(
+            "engine_version_mismatch: Snailer CLI {} does not satisfy {} required by this GUI (action: {}). Run snailer_cli_ensure_installed to {} the engine.",
+            self.installed.as_deref().unwrap_or("<unknown>"),
+            self.required,
+            self.action.as_deref().unwrap_or("install"),
+            self.action.as_deref().unwrap_or("install"),
+        )
+    }
+}
+
+/// JSON-RPC protocol revision spoken by `src/lib/daemon.ts`.
+const DAEMON_PROTOCOL_VERSION: u64 = 2;
+const DAEMON_REQUIRED_METHODS: &[&str] = &["session.list", "run.start", "approval.respond", "diff.get"];
+
+/// Send one JSON-RPC request over a fresh WebSocket and wait for its response.
+fn daemon_rpc_call_once(
+    port: u16,
+    method: &str,
+    params: serde_json::Value,
+    timeout: Duration,
+) -> Result<serde_json::Value, String> {
+    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+    let stream = std::net::TcpStream::connect_timeout(&addr, timeout)
+        .map_err(|e| format!("daemon connect failed: {}", e))?;
+    let _ = stream.set_read_timeout(Some(timeout));
+    let _ = stream.set_write_timeout(Some(timeout));
+    let (mut socket, _) = tungstenite::client(format!("ws://127.0.0.1:{}", port), stream)
+        .map_err(|e| format!("daemon websocket handshake failed: {}", e))?;
+
+    let req = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    socket
+        .send(tungstenite::Message::Text(req.to_string()))
+        .map_err(|e| format!("daemon send failed: {}", e))?;
+
+    let deadline = std::time::Instant::now() + timeout;
+    let result = loop {
+        if std::time::Instant::now() >= deadline {
+            break Err(format!("daemon did not answer {} within {:?}", method, timeout));
+        }
+        let msg = match socket.read() {
+            Ok(m) => m,
+            Err(e) => break Err(format!("daemon read failed: {}", e)),
+        };
+        let tungstenite::Message::Text(text) = msg else {
+            continue;
+        };
+        let Ok(v) = serde_json::from_str::<serde_json::Value>(&text) else {
+            continue;
+        };
+        // Skip notifications pushed before our response.
+        if v.get("id").and_then(|id| id.as_u64()) != Some(1) {
+            continue;
+        }
+        if let Some(err) = v.get("error") {
+            let msg = err.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error");
+            break Err(format!("daemon {} failed: {}", method, msg));
+        }
+        break Ok(v.get("result").cloned().unwrap_or(serde_json::Value::Null));
+    };
+    let _ = socket.close(None);
+    result
+}
+
+/// Check what the daemon advertises in its `initialize` result. Older daemons that do not
+/// advertise anything are accepted; an explicit mismatch is a typed error.
+fn check_daemon_capabilities(result: &serde_json::Value) -> Result<(), String> {
+    let protocol = result
+        .get("protocolVersion")
+        .or_else(|| result.get("server").and_then(|s| s.get("protocolVersion")))
+        .and_then(|v| v.as_u64().or_else(|| v.as_str().and_then(|s| s.trim().parse().ok())));
+    if let Some(p) = protocol {
+        if p != DAEMON_PROTOCOL_VERSION {
+            let action = if p < DAEMON_PROTOCOL_VERSION { "upgrade" } else { "downgrade" };
+            return Err(format!(
+                "engine_protocol_mismatch: daemon speaks protocol v{} but this GUI requires v{} (action: {}).",
+                p, DAEMON_PROTOCOL_VERSION, action
+            ));
+        }
+    }
+
+    let methods = result
+        .get("capabilities")
+        .and_then(|c| c.get("methods"))
+        .or_else(|| result.get("methods"))
+        .and_then(|m| m.as_array());
+    if let Some(methods) = methods {
+        let missing: Vec<&str> = DAEMON_REQUIRED_METHODS
+            .iter()
+            .copied()
+            .filter(|m| !methods.iter().any(|x| x.as_str() == Some(*m)))
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "engine_protocol_mismatch: daemon is missing required methods: {} (action: upgrade).",
+                missing.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+const DAEMON_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A daemon notification re-emitted on `engine://notification`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineNotification {
+    pub engine_id: String,
+    pub method: String,
+    pub params: serde_json::Value,
+}
+
+#[derive(Debug, PartialEq)]
+enum DaemonIncoming {
+    Response {
+        id: u64,
+        result: Result<serde_json::Value, String>,
+    },
+    Notification {
+        method: String,
+        params: serde_json::Value,
+    },
+}
+
+fn parse_daemon_message(text: &str) -> Option<DaemonIncoming> {
+    let v = serde_json::from_str::<serde_json::Value>(text).ok()?;
+    if v.get("jsonrpc").and_then(|j| j.as_str()) != Some("2.0") {
+        return None;
+    }
+    if let Some(id) = v.get("id").and_then(|id| id.as_u64()) {
+        let result = match v.get("error") {
+            Some(err) => Err(err
+                .get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("unknown error")
+                .to_string()),
+            None => Ok(v.get("result").cloned().unwrap_or(serde_json::Value::Null)),
+        };
+        return Some(DaemonIncoming::Response { id, result });
+    }
+    let method = v.get("method").and_then(|m| m.as_str())?.to_string();
+    let params = v.get("params").cloned().unwrap_or(serde_json::Value::Null);
+    Some(DaemonIncoming::Notification { method, params })
+}
+
+enum DaemonOutgoing {
+    Text(String),
+    Close,
+}
+
+type DaemonPending = Arc<Mutex<HashMap<u64, std::sync::mpsc::Sender<Result<serde_json::Value, String>>>>>;
+
+/// Long-lived, authenticated JSON-RPC connection to one engine's daemon.
+///
+/// A background thread owns the socket: it writes queued requests, routes responses to
+/// their callers and re-emits notifications on `engine://notification`.
+#[derive(Debug)]
+struct DaemonRpcClient {
+    outgoing: std::sync::mpsc::Sender<DaemonOutgoing>,
+    pending: DaemonPending,
+    next_id: std::sync::atomic::AtomicU64,
+    closed: Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl DaemonRpcClient {
+    fn connect(
+        app: tauri::AppHandle,
+        engine_id: &str,
+        port: u16,
+        token: &str,
+        project_path: &str,
+    ) -> Result<Arc<Self>, String> {
+        let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+        let stream = std::net::TcpStream::connect_timeout(&addr, Duration::from_secs(5))
+            .map_err(|e| format!("daemon connect failed: {}", e))?;
+        let _ = stream.set_write_timeout(Some(Duration::from_secs(5)));
+        let (socket, _) = tungstenite::client(format!("ws://127.0.0.1:{}", port), stream)
+            .map_err(|e| format!("daemon websocket handshake failed: {}", e))?;
+        // Short read timeout so the socket thread can interleave writes with reads.
+        let _ = socket.get_ref().set_read_timeout(Some(Duration::from_millis(50)));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let client = Arc::new(Self {
+            outgoing: tx,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            next_id: std::sync::atomic::AtomicU64::new(1),
+            closed: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        });
+        let pending = client.pending.clone();
+        let closed = client.closed.clone();
+        let engine_id_for_thread = engine_id.to_string();
+        std::thread::spawn(move || {
+            daemon_socket_loop(socket, rx, &pending, &app, &engine_id_for_thread);
+            closed.store(true, std::sync::atomic::Ordering::SeqCst);
+            if let Ok(mut pending) = pending.lock() {
+                for (_, waiter) in pending.drain() {
+                    let _ = waiter.send(Err("daemon connection closed".to_string()));
+                }
+            }
+        });
+
+        if let Err(e) = client.request(
+            "initialize",
+            serde_json::json!({ "token": token, "projectPath": project_path }),
+            Duration::from_secs(5),
+        ) {
+            client.close();
+            return Err(e);
+        }
+        Ok(client)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    fn request(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        timeout: Duration,
+    ) -> Result<serde_json::Value, String> {
+        if self.is_closed() {
+            return Err("daemon connection closed".to_string());
+        }
+        let id = self.next_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.pending
+            .lock()
+            .map_err(|_| "daemon pending lock poisoned".to_string())?
+            .insert(id, tx);
+        let req = serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if self.outgoing.send(DaemonOutgoing::Text(req.to_string())).is_err() {
+            if let Ok(mut pending) = self.pending.lock() {
+                pending.remove(&id);
+            }
+            return Err("daemon connection closed".to_string());
+        }
+        match rx.recv_timeout(timeout) {
+            Ok(result) => result.map_err(|e| format!("daemon {} failed: {}", method, e)),
+            Err(_) => {
+                if let Ok(mut pending) = self.pending.lock() {
+                    pending.remove(&id);
+                }
+                Err(format!("daemon did not answer {} within {:?}", method, timeout))
+            }
+        }
+    }
+
+    fn close(&self) {
+        let _ = self.outgoing.send(DaemonOutgoing::Close);
+    }
+}
+
+fn daemon_socket_loop(
+    mut socket: tungstenite::WebSocket<std::net::TcpStream>,
+    outgoing: std::sync::mpsc::Receiver<DaemonOutgoing>,
+    pending: &DaemonPending,
+    app: &tauri::AppHandle,
+    engine_id: &str,
+) {
+    loop {
+        loop {
+            match outgoing.try_recv() {
+                Ok(DaemonOutgoing::Text(text)) => {
+                    if socket.send(tungstenite::Message::Text(text)).is_err() {
+                        return;
+                    }
+                }
+                Ok(DaemonOutgoing::Close) | Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    let _ = socket.close(None);
+                    let _ = socket.flush();
+                    return;
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+            }
+        }
+
+        let text = match socket.read() {
+            Ok(tungstenite::Message::Text(text)) => text,
+            Ok(_) => continue,
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) =>
+            {
+                continue
+            }
+            Err(_) => return,
+        };
+        match parse_daemon_message(&text) {
+            Some(DaemonIncoming::Response { id, result }) => {
+                let waiter = pending.lock().ok().and_then(|mut p| p.remove(&id));
+                if let Some(waiter) = waiter {
+                    let _ = waiter.send(result);
+                }
+            }
+            Some(DaemonIncoming::Notification { method, params }) => {
+                let _ = app.emit(
+                    "engine://notification",
+                    EngineNotification {
+                        engine_id: engine_id.to_string(),
+                        method,
+                        params,
+                    },
+                );
+            }
+            None => {}
+        }
+    }
+}
+
+fn gui_settings_path() -> PathBuf {
+    snailer_home_dir().join("gui_settings.json")
+}
+
+/// Current GUI settings. Background readers fall back to defaults if the file is malformed;
+/// `settings_get` surfaces the error instead.
+fn gui_settings() -> GuiSettings {
+    crate::settings::load(&gui_settings_path()).unwrap_or_else(|e| {
+        log::warn!("{}", e);
+        GuiSettings::default()
+    })
+}
+
+fn update_gui_settings(f: impl FnOnce(&mut GuiSettings)) -> Result<GuiSettings, String> {
+    crate::settings::update(&gui_settings_path(), |s| {
+        f(s);
+        Ok(())
+    })
+}
+
+fn shared_env_path() -> Result<PathBuf, String> {
+    let dir = snailer_home_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| format!("mkdir failed: {}", e))?;
+    Ok(dir.join(".env"))
+}
+
+/// The env file GUI-launched daemons get as `SNAILER_ENV_FILE`. Kept here instead of in the
+/// process environment so the settings watcher thread can retarget it without racing
+/// `std::env` readers. Seeded from the environment the GUI itself was launched with.
+fn active_env_file() -> &'static Mutex<Option<PathBuf>> {
+    static ACTIVE: OnceLock<Mutex<Option<PathBuf>>> = OnceLock::new();
+    ACTIVE.get_or_init(|| Mutex::new(std::env::var_os("SNAILER_ENV_FILE").map(PathBuf::from)))
+}
+
+fn current_env_file() -> Option<PathBuf> {
+    active_env_file().lock().ok().and_then(|g| g.clone())
+}
+
+fn set_current_env_file(path: Option<PathBuf>) {
+    if let Ok(mut guard) = active_env_file().lock() {
+        *guard = path;
+    }
+}
+
+fn ensure_shared_env_selected() -> Result<PathBuf, String> {
+    let p = active_env_path()?;
+    if !p.exists() {
+        // Under the lock, so a file created concurrently keeps its contents.
+        crate::persist::update(&p, env_write_policy(&p), |current| Ok(current.unwrap_or_default()))?;
+    }
+    set_current_env_file(Some(p.clone()));
+    let env_file = p.to_string_lossy().to_string();
+    update_gui_settings(|s| s.snailer_env_file = Some(env_file))?;
+    Ok(p)
+}
+
+/// Return the default shared `.env` path used by the GUI: `~/.snailer/.env`.
+#[tauri::command]
+pub async fn env_global_path() -> Result<String, String> {
+    Ok(shared_env_path()?.to_string_lossy().to_string())
+}
+
+/// Get the currently configured `SNAILER_ENV_FILE` for this GUI (persisted under `~/.snailer/gui_settings.json`).
+#[tauri::command]
+pub async fn snailer_env_file_get() -> Result<Option<String>, String> {
+    Ok(gui_settings().snailer_env_file)
+}
+
+/// Set (or clear) the `SNAILER_ENV_FILE` used by Snailer when launched from this GUI.
+/// Takes effect immediately for engines started afterwards.
+#[tauri::command]
+pub async fn snailer_env_file_set(path: Option<String>) -> Result<Option<String>, String> {
+    match path.as_deref() {
+        Some(p) if !p.trim().is_empty() => {
+            set_current_env_file(Some(PathBuf::from(p)));
+            update_gui_settings(|s| s.snailer_env_file = Some(p.to_string()))?;
+            Ok(Some(p.to_string()))
+        }
+        _ => {
+            set_current_env_file(None);
+            update_gui_settings(|s| s.snailer_env_file = None)?;
+            Ok(None)
+        }
+    }
+}
+
+/// Domain checks on top of serde's type checks, normalizing values the same way the
+/// per-setting commands do. Only keys present in `patch` are checked, so a saved path that has
+/// since disappeared doesn't block unrelated updates.
+fn validate_gui_settings(s: &mut GuiSettings, patch: &serde_json::Map<String, serde_json::Value>) -> Result<(), String> {
+    let patched = |key: &str| patch.contains_key(key);
+    if patched("authAddr") {
+        if let Some(addr) = s.auth_addr.as_deref() {
+            s.auth_addr = Some(normalize_legacy_auth_addr(addr));
+        }
+    }
+    if patched("npmRegistry") {
+        s.npm_registry = s.npm_registry.as_deref().map(normalize_registry_url).transpose()?;
+    }
+    if patched("npmScopeRegistry") {
+        s.npm_scope_registry = s.npm_scope_registry.as_deref().map(normalize_registry_url).transpose()?;
+    }
+    if let Some(dir) = s.offline_bundle_dir.as_deref().filter(|_| patched("offlineBundleDir")) {
+        if !Path::new(dir).is_dir() {
+            return Err(format!("offlineBundleDir is not a directory: {}", dir));
+        }
+    }
+    if let Some(file) = s.snailer_env_file.as_deref().filter(|_| patched("snailerEnvFile")) {
+        if !Path::new(file).parent().is_some_and(|d| d.is_dir()) {
+            return Err(format!("snailerEnvFile parent directory does not exist: {}", file));
+        }
+    }
+    if patched("activeEnvProfile") {
+        if s.active_env_profile.as_deref() == Some(DEFAULT_ENV_PROFILE) {
+            s.active_env_profile = None;
+        }
+        if let Some(name) = s.active_env_profile.as_deref() {
+            if !env_profile_path(name)?.is_file() {
+                return Err(format!("activeEnvProfile '{}' does not exist", name));
+            }
+        }
+    }
+    if let Some(limits) = s.engine_limits.as_ref().filter(|_| patched("engineLimits")) {
+        if limits.max_cpu_percent.is_some_and(|c| !c.is_finite() || c <= 0.0) {
+            return Err("engineLimits.maxCpuPercent must be a positive number".to_string());
+        }
+        if limits.max_rss_mb == Some(0) || limits.max_open_fds == Some(0) {
+            return Err("engineLimits values must be greater than zero".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Read `gui_settings.json` (migrated to the current schema). Errors if the file is malformed.
+#[tauri::command]
+pub async fn settings_get() -> Result<GuiSettings, String> {
+    crate::settings::load(&gui_settings_path())
+}
+
+/// Apply a partial update to `gui_settings.json`: known keys only, `null` resets a key.
+#[tauri::command]
+pub async fn settings_patch(patch: serde_json::Value) -> Result<GuiSettings, String> {
+    let keys = patch.as_object().cloned().unwrap_or_default();
+    let updated = crate::settings::update(&gui_settings_path(), |s| {
+        let mut next = crate::settings::apply_patch(s, &patch)?;
+        validate_gui_settings(&mut next, &keys)?;
+        *s = next;
+        Ok(())
+    })?;
+    // Keep the active env file in step, as `snailer_env_file_set` does.
+    if patch.get("snailerEnvFile").is_some() {
+        set_current_env_file(updated.snailer_env_file.as_deref().map(PathBuf::from));
+    }
+    Ok(updated)
+}
+
+/// Settings holding machine-specific paths; exported relative to the home directory.
+const PORTABLE_PATH_SETTINGS: &[&str] = &["snailerEnvFile", "offlineBundleDir"];
+
+fn portable_home_path(path: &str) -> String {
+    match Path::new(path).strip_prefix(home_dir()) {
+        Ok(rel) => format!("~/{}", rel.to_string_lossy().replace('\\', "/")),
+        Err(_) => path.to_string(),
+    }
+}
+
+fn expand_home_path(path: &str) -> String {
+    match path.strip_prefix("~/") {
+        Some(rel) => home_dir().join(rel).to_string_lossy().to_string(),
+        None => path.to_string(),
+    }
+}
+
+fn settings_object(settings: &GuiSettings) -> serde_json::Map<String, serde_json::Value> {
+    match serde_json::to_value(settings) {
+        Ok(serde_json::Value::Object(obj)) => obj,
+        _ => serde_json::Map::new(),
+    }
+}
+
+/// First occurrence of each key in an env file (the one that takes effect).
+fn env_file_secrets(path: &Path) -> Result<std::collections::BTreeMap<String, String>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read {}: {}", display_snailer_path(path), e)),
+    };
+    let mut out = std::collections::BTreeMap::new();
+    for entry in crate::dotenv::EnvFile::parse(&text).entries() {
+        if !entry.value.trim().is_empty() {
+            out.entry(entry.key.clone()).or_insert_with(|| entry.value.clone());
+        }
+    }
+    Ok(out)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigExportResponse {
+    pub path: String,
+    pub secrets_included: bool,
+    /// Settings left out because their names look secret.
+    pub skipped_keys: Vec<String>,
+}
+
+fn bundle_budget_caps(
+    caps: &std::collections::BTreeMap<String, BudgetCap>,
+) -> std::collections::BTreeMap<String, crate::config_bundle::BudgetCap> {
+    caps.iter()
+        .map(|(id, cap)| {
+            let cap = crate::config_bundle::BudgetCap {
+                daily_limit: cap.daily_limit,
+                monthly_limit: cap.monthly_limit,
+            };
+            (id.clone(), cap)
+        })
+        .collect()
+}
+
+/// Validate imported caps the way `budget_set_agent_limit` / `budget_set_provider_limit` do.
+fn imported_budget_caps(
+    section: &str,
+    caps: &std::collections::BTreeMap<String, crate::config_bundle::BudgetCap>,
+    lowercase_ids: bool,
+) -> Result<std::collections::BTreeMap<String, BudgetCap>, String> {
+    let mut out = std::collections::BTreeMap::new();
+    for (id, cap) in caps {
+        let id = budget_id(id, &format!("budget.{} id", section))?;
+        let id = if lowercase_ids { id.to_lowercase() } else { id };
+        let cap = budget_cap(cap.daily_limit, cap.monthly_limit).map_err(|e| format!("budget.{}.{}: {}", section, id, e))?;
+        set_budget_cap(&mut out, id, cap);
+    }
+    Ok(out)
+}
+
+/// Write a portable config bundle: GUI settings, budget limits and the UI's `frontend` section
+/// (router/orchestrator defaults). Secrets are only included, encrypted, when a passphrase is given.
+#[tauri::command]
+pub async fn config_export(
+    path: String,
+    passphrase: Option<String>,
+    frontend: Option<serde_json::Value>,
+) -> Result<ConfigExportResponse, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let out = PathBuf::from(path.trim());
+        if path.trim().is_empty() {
+            return Err("path is empty".to_string());
+        }
+        let mut gui = settings_object(&crate::settings::load(&gui_settings_path())?);
+        let mut skipped_keys = Vec::new();
+        gui.retain(|key, _| {
+            let keep = !is_secret_key_name(key);
+            if !keep {
+                skipped_keys.push(key.clone());
+            }
+            keep
+        });
+        for key in PORTABLE_PATH_SETTINGS {
+            if let Some(serde_json::Value::String(p)) = gui.get_mut(*key) {
+                *p = portable_home_path(p);
+            }
+        }
+        // Effective limits, so the bundle shows what was actually enforced here.
+        let (plan, env_main_override, env_minimax_override) = budget_env();
+        let budget = crate::persist::read_json::<BudgetState>(&budget_state_path()?).map(|_| {
+            let s = read_budget_state(&plan, env_main_override, env_minimax_override);
+            crate::config_bundle::BudgetLimits {
+                monthly_limit_main: s.monthly_limit_main,
+                monthly_limit_minimax: s.monthly_limit_minimax,
+                agent_limits: Some(bundle_budget_caps(&s.agent_limits)),
+                provider_limits: Some(bundle_budget_caps(&s.provider_limits)),
+            }
+        });
+        let secrets = match non_empty_trimmed(passphrase) {
+            Some(pass) => {
+                let secrets = crate::config_bundle::Secrets {
+                    env: env_file_secrets(&active_env_path()?)?,
+                    npm_registry_token: npm_token_keychain_get(),
+                };
+                Some(crate::config_bundle::encrypt_secrets(&secrets, &pass)?)
+            }
+            None => None,
+        };
+        let secrets_included = secrets.is_some();
+        let bundle = crate::config_bundle::ConfigBundle {
+            format: crate::config_bundle::FORMAT.to_string(),
+            version: crate::config_bundle::VERSION,
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            gui_settings: gui,
+            budget,
+            frontend,
+            secrets,
+        };
+        let text = serde_json::to_string_pretty(&bundle).map_err(|e| format!("serialize failed: {}", e))?;
+        crate::persist::write(&out, &text, crate::persist::Policy::TEXT_NO_BACKUP)?;
+        chmod_600(&out);
+        Ok(ConfigExportResponse {
+            path: out.to_string_lossy().to_string(),
+            secrets_included,
+            skipped_keys,
+        })
+    })
+    .await
+    .map_err(|e| format!("config export task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigImportResponse {
+    /// What will change (or changed, when `applied`). Secret values are masked.
+    pub changes: Vec<crate::config_bundle::ConfigChange>,
+    /// Settings that were skipped, e.g. paths that don't exist on this machine.
+    pub warnings: Vec<String>,
+    pub applied: bool,
+    /// The bundle's UI section, for the frontend to apply to its own store.
+    pub frontend: Option<serde_json::Value>,
+    pub secrets_included: bool,
+}
+
+/// Restore a file captured before an import; `None` means it didn't exist.
+fn restore_file(path: &Path, original: Option<String>, policy: crate::persist::Policy) {
+    let result = match original {
+        Some(text) => crate::persist::write(path, &text, policy),
+        None => match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        },
+    };
+    if let Err(e) = result {
+        log::warn!("config import rollback failed for {}: {}", path.display(), e);
+    }
+}
+
+/// Validate a config bundle and preview the changes it makes. With `apply: true` the changes
+/// are written; if any write fails, files already written are restored.
+#[tauri::command]
+pub async fn config_import(
+    path: String,
+    passphrase: Option<String>,
+    apply: Option<bool>,
+) -> Result<ConfigImportResponse, String> {
+    use crate::config_bundle::ConfigChange;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let text = std::fs::read_to_string(path.trim()).map_err(|e| format!("read failed: {}", e))?;
+        let bundle = crate::config_bundle::parse(&text)?;
+        let mut changes = Vec::new();
+        let mut warnings = Vec::new();
+
+        // GUI settings: key by key, so one setting that doesn't fit this machine doesn't block the rest.
+        let settings_path = gui_settings_path();
+        let current = crate::settings::load(&settings_path)?;
+        let mut next = current.clone();
+        for (key, value) in &bundle.gui_settings {
+            if key == "schemaVersion" || is_secret_key_name(key) {
+                continue;
+            }
+            let mut value = value.clone();
+            if PORTABLE_PATH_SETTINGS.contains(&key.as_str()) {
+                if let serde_json::Value::String(p) = &value {
+                    value = serde_json::Value::String(expand_home_path(p));
+                }
+            }
+            let mut patch = serde_json::Map::new();
+            patch.insert(key.clone(), value.clone());
+            let candidate = match crate::settings::apply_patch(&next, &serde_json::Value::Object(patch.clone())) {
+                Err(e) if e.starts_with("unknown setting") => {
+                    // Written by a newer build; keep it verbatim like any other unknown key.
+                    next.extra.insert(key.clone(), value);
+                    continue;
+                }
+                other => other.and_then(|mut c| validate_gui_settings(&mut c, &patch).map(|_| c)),
+            };
+            match candidate {
+                Ok(c) => next = c,
+                Err(e) => warnings.push(format!("{} not imported: {}", key, e)),
+            }
+        }
+        changes.extend(crate::config_bundle::diff_objects(
+            "guiSettings",
+            &settings_object(&current),
+            &settings_object(&next),
+        ));
+
+        // Budget limits. The main/MiniMax monthly limits follow SNAILER_PLAN and the
+        // SNAILER_BUDGET_* env vars on every read, so they're reported rather than imported.
+        let budget_path = budget_state_path()?;
+        let current_budget = crate::persist::read_json::<BudgetState>(&budget_path);
+        if let Some(limits) = bundle.budget.as_ref() {
+            let (plan, env_main_override, env_minimax_override) = budget_env();
+            let effective = read_budget_state(&plan, env_main_override, env_minimax_override);
+            for (key, here, bundled) in [
+                ("monthlyLimitMain", effective.monthly_limit_main, limits.monthly_limit_main),
+                ("monthlyLimitMinimax", effective.monthly_limit_minimax, limits.monthly_limit_minimax),
+            ] {
+                if here != bundled {
+                    warnings.push(format!(
+                        "budget.{} not imported ({} here, {} in the bundle): it follows SNAILER_PLAN and SNAILER_BUDGET_MAIN / SNAILER_BUDGET_MINIMAX",
+                        key, here, bundled
+                    ));
+                }
+            }
+        }
+        // Per-agent/provider caps replace the local ones wholesale; bundles without them leave
+        // the local caps alone.
+        let caps_object = |caps: &std::collections::BTreeMap<String, BudgetCap>| match serde_json::to_value(bundle_budget_caps(caps)) {
+            Ok(serde_json::Value::Object(obj)) => obj,
+            _ => serde_json::Map::new(),
+        };
+        let mut imported_agent_limits = None;
+        let mut imported_provider_limits = None;
+        if let Some(limits) = bundle.budget.as_ref() {
+            let current_agent = current_budget.as_ref().map(|s| &s.agent_limits);
+            let current_provider = current_budget.as_ref().map(|s| &s.provider_limits);
+            for (section, current_caps, imported, lowercase_ids, slot) in [
+                ("agentLimits", current_agent, limits.agent_limits.as_ref(), false, &mut imported_agent_limits),
+                ("providerLimits", current_provider, limits.provider_limits.as_ref(), true, &mut imported_provider_limits),
+            ] {
+                let Some(imported) = imported else {
+                    continue;
+                };
+                let imported = imported_budget_caps(section, imported, lowercase_ids)?;
+                let current_caps = current_caps.map(caps_object).unwrap_or_default();
+                changes.extend(
+                    crate::config_bundle::diff_objects("budget", &current_caps, &caps_object(&imported))
+                        .into_iter()
+                        .map(|c| ConfigChange {
+                            key: format!("{}.{}", section, c.key),
+                            ..c
+                        }),
+                );
+                *slot = Some(imported);
+            }
+        }
+
+        // Secrets: never returned in the clear.
+        let secrets = match (bundle.secrets.as_ref(), non_empty_trimmed(passphrase)) {
+            (Some(enc), Some(pass)) => Some(crate::config_bundle::decrypt_secrets(enc, &pass)?),
+            (Some(_), None) => {
+                warnings.push("bundle contains encrypted secrets; enter the passphrase to import them".to_string());
+                None
+            }
+            (None, _) => None,
+        };
+        // Secrets go to the env file that will be active once the imported settings apply.
+        let env_path = env_profile_path(&active_env_profile_in(&next))?;
+        let mut env_updates = Vec::new();
+        let mut npm_token = None;
+        if let Some(secrets) = secrets.as_ref() {
+            let current_env = env_file_secrets(&env_path)?;
+            for (key, value) in &secrets.env {
+                if !crate::dotenv::is_valid_key(key) {
+                    warnings.push(format!("{} not imported: invalid env var name", key));
+                    continue;
+                }
+                let from = current_env.get(key);
+                if from != Some(value) {
+                    changes.push(ConfigChange {
+                        section: "env".to_string(),
+                        key: key.clone(),
+                        from: from.map(|v| serde_json::Value::from(mask_env_value(v))),
+                        to: Some(serde_json::Value::from(mask_env_value(value))),
+                    });
+                    env_updates.push((key.clone(), value.clone()));
+                }
+            }
+            if let Some(token) = secrets.npm_registry_token.as_ref() {
+                let from = npm_token_keychain_get();
+                if from.as_ref() != Some(token) {
+                    changes.push(ConfigChange {
+                        section: "secrets".to_string(),
+                        key: "npmRegistryToken".to_string(),
+                        from: from.map(|v| serde_json::Value::from(mask_env_value(&v))),
+                        to: Some(serde_json::Value::from(mask_env_value(token))),
+                    });
+                    npm_token = Some(token.clone());
+                }
+            }
+        }
+
+        let apply = apply.unwrap_or(false) && !changes.is_empty();
+        if apply {
+            let env_policy = env_write_policy(&env_path);
+            let originals = [
+                (settings_path.clone(), crate::persist::read(&settings_path, crate::persist::Policy::JSON), crate::persist::Policy::JSON),
+                (budget_path.clone(), crate::persist::read(&budget_path, crate::persist::Policy::JSON), crate::persist::Policy::JSON),
+                (env_path.clone(), crate::persist::read(&env_path, env_policy), env_policy),
+            ];
+            let result = (|| {
+                let imported = next.clone();
+                crate::settings::update(&settings_path, |s| {
+                    *s = imported;
+                    Ok(())
+                })?;
+                if imported_agent_limits.is_some() || imported_provider_limits.is_some() {
+                    update_budget_state(|state| {
+                        if let Some(caps) = imported_agent_limits.clone() {
+                            state.agent_limits = caps;
+                        }
+                        if let Some(caps) = imported_provider_limits.clone() {
+                            state.provider_limits = caps;
+                        }
+                        Ok(())
+                    })?;
+                }
+                if !env_updates.is_empty() {
+                    crate::persist::update(&env_path, env_policy, |existing| {
+                        let mut env = crate::dotenv::EnvFile::parse(&existing.unwrap_or_default());
+                        for (key, value) in &env_updates {
+                            env.set(key, value);
+                        }
+                        Ok(env.to_string())
+                    })?;
+                }
+                // Last, since the keychain can't be rolled back with the files.
+                if let Some(token) = npm_token.as_deref() {
+                    npm_token_keychain_entry()?
+                        .set_password(token)
+                        .map_err(|e| format!("keychain write failed: {}", e))?;
+                }
+                Ok::<(), String>(())
+            })();
+            if let Err(e) = result {
+                for (p, original, policy) in originals {
+                    restore_file(&p, original, policy);
+                }
+                return Err(format!("import failed, nothing was changed: {}", e));
             }
-            Err(e) => {
-                let _ = child.kill();
-                let _ = child.wait();
-                return Err(format!("Failed while waiting {:?}: {}", program, e));
+            if next.snailer_env_file != current.snailer_env_file {
+                set_current_env_file(next.snailer_env_file.as_deref().map(PathBuf::from));
             }
         }
-    }
-}
 
-fn snailer_cli_health_ok(bin_path: &Path, extra_path: Option<&Path>) -> Result<bool, String> {
-    if !run_quick_cmd(bin_path, &["--version"], extra_path)? {
-        return Ok(false);
-    }
-    if !run_quick_cmd(bin_path, &["daemon", "--help"], extra_path)? {
-        return Ok(false);
-    }
-    Ok(true)
+        Ok(ConfigImportResponse {
+            changes,
+            warnings,
+            applied: apply,
+            frontend: bundle.frontend,
+            secrets_included: bundle.secrets.is_some(),
+        })
+    })
+    .await
+    .map_err(|e| format!("config import task failed: {}", e))?
 }
 
-fn gui_settings_path() -> PathBuf {
-    snailer_home_dir().join("gui_settings.json")
-}
+/// Reserved profile name for the shared `~/.snailer/.env`.
+const DEFAULT_ENV_PROFILE: &str = "default";
 
-fn read_gui_settings_env_file() -> Option<String> {
-    let path = gui_settings_path();
-    let Ok(text) = std::fs::read_to_string(&path) else {
-        return None;
-    };
-    let Ok(v) = serde_json::from_str::<serde_json::Value>(&text) else {
-        return None;
-    };
-    v.get("snailerEnvFile")
-        .and_then(|x| x.as_str())
-        .map(|s| s.to_string())
-        .filter(|s| !s.trim().is_empty())
+fn env_profiles_dir() -> PathBuf {
+    snailer_home_dir().join("profiles")
 }
 
-fn read_gui_settings_auth_addr() -> Option<String> {
-    let path = gui_settings_path();
-    let Ok(text) = std::fs::read_to_string(&path) else {
-        return None;
-    };
-    let Ok(v) = serde_json::from_str::<serde_json::Value>(&text) else {
-        return None;
-    };
-    v.get("authAddr")
-        .and_then(|x| x.as_str())
-        .map(|s| s.to_string())
-        .filter(|s| !s.trim().is_empty())
+fn is_valid_env_profile_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
 }
 
-fn write_gui_settings_env_file(value: Option<&str>) -> Result<(), String> {
-    let path = gui_settings_path();
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent).map_err(|e| format!("mkdir failed: {}", e))?;
+fn env_profile_path(name: &str) -> Result<PathBuf, String> {
+    if !is_valid_env_profile_name(name) {
+        return Err(format!(
+            "invalid profile name '{}': use letters, digits, '-' or '_'",
+            name
+        ));
     }
-    let mut obj = if let Ok(text) = std::fs::read_to_string(&path) {
-        serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&text).unwrap_or_default()
-    } else {
-        serde_json::Map::new()
-    };
-    if let Some(v) = value {
-        obj.insert(
-            "snailerEnvFile".to_string(),
-            serde_json::Value::String(v.to_string()),
-        );
-    } else {
-        obj.insert("snailerEnvFile".to_string(), serde_json::Value::Null);
+    if name == DEFAULT_ENV_PROFILE {
+        return shared_env_path();
     }
-    let text = serde_json::Value::Object(obj).to_string();
-    std::fs::write(&path, text).map_err(|e| format!("write failed: {}", e))?;
-    Ok(())
+    Ok(env_profiles_dir().join(format!("{}.env", name)))
 }
 
-fn write_gui_settings_auth_addr(value: Option<&str>) -> Result<(), String> {
-    let path = gui_settings_path();
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent).map_err(|e| format!("mkdir failed: {}", e))?;
-    }
+/// Active profile name from `gui_settings.json`; a missing profile file falls back to the default.
+fn active_env_profile() -> String {
+    active_env_profile_in(&gui_settings())
+}
 
-    let mut obj = if let Ok(text) = std::fs::read_to_string(&path) {
-        serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&text).unwrap_or_default()
-    } else {
-        serde_json::Map::new()
-    };
+fn active_env_profile_in(settings: &GuiSettings) -> String {
+    settings
+        .active_env_profile
+        .clone()
+        .filter(|name| name != DEFAULT_ENV_PROFILE)
+        .filter(|name| env_profile_path(name).map(|p| p.is_file()).unwrap_or(false))
+        .unwrap_or_else(|| DEFAULT_ENV_PROFILE.to_string())
+}
 
-    if let Some(v) = value {
-        obj.insert("authAddr".to_string(), serde_json::Value::String(v.to_string()));
-    } else {
-        obj.insert("authAddr".to_string(), serde_json::Value::Null);
+/// `.env` file of the active profile (`~/.snailer/.env` for the default profile).
+fn active_env_path() -> Result<PathBuf, String> {
+    env_profile_path(&active_env_profile())
+}
+
+/// Render paths under `~/.snailer` as `~/.snailer/...` for user-facing messages.
+fn display_snailer_path(path: &Path) -> String {
+    match path.strip_prefix(snailer_home_dir()) {
+        Ok(rel) => format!("~/.snailer/{}", rel.to_string_lossy()),
+        Err(_) => path.to_string_lossy().to_string(),
     }
-    let text = serde_json::Value::Object(obj).to_string();
-    std::fs::write(&path, text).map_err(|e| format!("write failed: {}", e))?;
-    Ok(())
 }
 
-fn shared_env_path() -> Result<PathBuf, String> {
-    let dir = snailer_home_dir();
-    std::fs::create_dir_all(&dir).map_err(|e| format!("mkdir failed: {}", e))?;
-    Ok(dir.join(".env"))
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvProfileInfo {
+    pub name: String,
+    pub path: String,
+    pub active: bool,
+    pub key_count: usize,
 }
 
-fn ensure_shared_env_selected() -> Result<PathBuf, String> {
-    let p = shared_env_path()?;
-    if !p.exists() {
-        std::fs::write(&p, b"").map_err(|e| format!("write failed: {}", e))?;
-    }
-    std::env::set_var("SNAILER_ENV_FILE", &p);
-    write_gui_settings_env_file(Some(&p.to_string_lossy()))?;
-    Ok(p)
+fn env_profile_info(name: &str, active: &str) -> Result<EnvProfileInfo, String> {
+    let path = env_profile_path(name)?;
+    let key_count = std::fs::read_to_string(&path)
+        .map(|text| {
+            let env = crate::dotenv::EnvFile::parse(&text);
+            env.entries().map(|e| &e.key).collect::<std::collections::HashSet<_>>().len()
+        })
+        .unwrap_or(0);
+    Ok(EnvProfileInfo {
+        name: name.to_string(),
+        path: path.to_string_lossy().to_string(),
+        active: name == active,
+        key_count,
+    })
 }
 
-/// Return the default shared `.env` path used by the GUI: `~/.snailer/.env`.
+/// List env profiles: `default` (`~/.snailer/.env`) followed by `~/.snailer/profiles/*.env`.
 #[tauri::command]
-pub async fn env_global_path() -> Result<String, String> {
-    Ok(shared_env_path()?.to_string_lossy().to_string())
+pub async fn env_profile_list() -> Result<Vec<EnvProfileInfo>, String> {
+    let active = active_env_profile();
+    let mut names: Vec<String> = std::fs::read_dir(env_profiles_dir())
+        .map(|rd| {
+            rd.filter_map(|e| e.ok())
+                .filter_map(|e| {
+                    let path = e.path();
+                    if path.extension().and_then(|x| x.to_str()) != Some("env") {
+                        return None;
+                    }
+                    path.file_stem().and_then(|s| s.to_str()).map(|s| s.to_string())
+                })
+                .filter(|name| is_valid_env_profile_name(name) && name != DEFAULT_ENV_PROFILE)
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    std::iter::once(DEFAULT_ENV_PROFILE.to_string())
+        .chain(names)
+        .map(|name| env_profile_info(&name, &active))
+        .collect()
 }
 
-/// Get the currently configured `SNAILER_ENV_FILE` for this GUI (persisted under `~/.snailer/gui_settings.json`).
+/// Create a profile, optionally seeded with a copy of another profile's keys.
 #[tauri::command]
-pub async fn snailer_env_file_get() -> Result<Option<String>, String> {
-    Ok(read_gui_settings_env_file())
+pub async fn env_profile_create(name: String, copy_from: Option<String>) -> Result<EnvProfileInfo, String> {
+    let name = name.trim();
+    if name == DEFAULT_ENV_PROFILE {
+        return Err("the default profile always exists".to_string());
+    }
+    let path = env_profile_path(name)?;
+    if path.exists() {
+        return Err(format!("profile '{}' already exists", name));
+    }
+    let seed = match non_empty_trimmed(copy_from) {
+        Some(src) => std::fs::read_to_string(env_profile_path(&src)?)
+            .map_err(|e| format!("failed to read profile '{}': {}", src, e))?,
+        None => String::new(),
+    };
+    std::fs::create_dir_all(env_profiles_dir()).map_err(|e| format!("mkdir failed: {}", e))?;
+    crate::persist::write(&path, &seed, env_write_policy(&path))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+    }
+    env_profile_info(name, &active_env_profile())
 }
 
-/// Set (or clear) the `SNAILER_ENV_FILE` used by Snailer when launched from this GUI.
-/// Also sets the process env var immediately so subsequent calls can observe it.
+/// Make `name` the active profile: persists it, points `SNAILER_ENV_FILE` at it and restarts
+/// running engines that were using the previous profile so the daemon picks up the new keys.
 #[tauri::command]
-pub async fn snailer_env_file_set(path: Option<String>) -> Result<Option<String>, String> {
-    match path.as_deref() {
-        Some(p) if !p.trim().is_empty() => {
-            std::env::set_var("SNAILER_ENV_FILE", p);
-            write_gui_settings_env_file(Some(p))?;
-            Ok(Some(p.to_string()))
-        }
-        _ => {
-            std::env::remove_var("SNAILER_ENV_FILE");
-            write_gui_settings_env_file(None)?;
-            Ok(None)
-        }
+pub async fn env_profile_switch(app: tauri::AppHandle, name: String) -> Result<EnvProfileInfo, String> {
+    let name = name.trim();
+    let path = env_profile_path(name)?;
+    if !path.is_file() && name != DEFAULT_ENV_PROFILE {
+        return Err(format!("profile '{}' does not exist", name));
+    }
+    let previous = active_env_path().ok().map(|p| p.to_string_lossy().to_string());
+
+    let stored = (name != DEFAULT_ENV_PROFILE).then(|| name.to_string());
+    update_gui_settings(|s| s.active_env_profile = stored)?;
+    let selected = ensure_shared_env_selected()?;
+    let selected = selected.to_string_lossy().to_string();
+
+    let to_switch: Vec<(String, Option<Arc<DaemonRpcClient>>)> = match engines().lock() {
+        Ok(mut guard) => guard
+            .values_mut()
+            .filter(|st| st.env_file.is_some() && st.env_file == previous)
+            .map(|st| {
+                st.env_file = Some(selected.clone());
+                (st.id.clone(), st.rpc.clone())
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    if previous.as_deref() != Some(selected.as_str()) && !to_switch.is_empty() {
+        // Switch live via `env.reload` where the daemon supports it; restart the rest.
+        std::thread::spawn(move || {
+            let params = serde_json::json!({ "envFile": selected });
+            for (id, rpc) in to_switch {
+                let reloaded = rpc.is_some_and(|client| {
+                    client.request("env.reload", params.clone(), Duration::from_secs(5)).is_ok()
+                });
+                if !reloaded {
+                    restart_engine(&app, &id, "env-profile-switched");
+                }
+            }
+        });
+    }
+    env_profile_info(name, name)
+}
+
+/// Delete a profile. The default profile and the active profile cannot be deleted.
+#[tauri::command]
+pub async fn env_profile_delete(name: String) -> Result<(), String> {
+    let name = name.trim();
+    if name == DEFAULT_ENV_PROFILE {
+        return Err("the default profile cannot be deleted".to_string());
+    }
+    if name == active_env_profile() {
+        return Err(format!("profile '{}' is active; switch to another profile first", name));
     }
+    let path = env_profile_path(name)?;
+    std::fs::remove_file(&path).map_err(|e| format!("failed to delete profile '{}': {}", name, e))
 }
 
 fn default_auth_addr() -> Option<String> {
@@ -556,7 +2565,7 @@ fn resolve_auth_addr() -> Result<String, String> {
         }
     }
 
-    if let Some(v) = read_gui_settings_auth_addr() {
+    if let Some(v) = gui_settings().auth_addr {
         if !v.trim().is_empty() {
             return Ok(normalize_legacy_auth_addr(&v));
         }
@@ -1332,22 +3341,215 @@ fn openai_exchange_code_for_tokens(
         .map_err(|e| format!("Invalid OpenAI token response: {}", e))
 }
 
-fn validate_openai_bearer_token(token: &str, api_base: &str) -> Result<(), String> {
-    let req = ureq::get(&format!("{}/v1/models", api_base))
-        .set("Authorization", &format!("Bearer {}", token.trim()));
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderAccountInfo {
+    pub organization: Option<String>,
+    pub name: Option<String>,
+    pub balance: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderKeyValidation {
+    pub provider: String,
+    /// Env var the key was read from, if it wasn't passed in.
+    pub env_var: Option<String>,
+    /// "valid" | "invalid" | "quota-exceeded" | "http-error" | "network-error" | "missing".
+    /// "http-error" is any other status the provider answered with (see `http_status`);
+    /// "network-error" means no HTTP response at all.
+    pub status: String,
+    pub http_status: Option<u16>,
+    pub message: Option<String>,
+    pub account: Option<ProviderAccountInfo>,
+}
+
+/// Env vars each provider's key is read from, in priority order.
+fn provider_key_env_vars(provider: &str) -> Option<&'static [&'static str]> {
+    match provider {
+        "openai" => Some(&["OPENAI_API_KEY"]),
+        "anthropic" | "claude" => Some(&["ANTHROPIC_API_KEY", "CLAUDE_API_KEY"]),
+        "xai" | "grok" => Some(&["XAI_API_KEY"]),
+        "moonshot" | "kimi" => Some(&["MOONSHOT_API_KEY"]),
+        "google" | "gemini" => Some(&["GOOGLE_API_KEY", "GEMINI_API_KEY"]),
+        _ => None,
+    }
+}
+
+/// Map an HTTP failure to a validation status. Providers disagree on codes for exhausted
+/// credit (402, 429, or 403 with a billing message) and for bad keys (Google and xAI answer
+/// 400 with an "API key" message), so the body is consulted too.
+fn classify_provider_http_error(code: u16, body: &str) -> &'static str {
+    let b = body.to_lowercase();
+    let mentions_quota = ["quota", "credit", "billing", "balance", "insufficient_funds", "rate limit", "exceeded"]
+        .iter()
+        .any(|needle| b.contains(needle));
+    let mentions_key = ["api key", "api_key", "apikey", "x-api-key"].iter().any(|needle| b.contains(needle));
+    match code {
+        402 | 429 => "quota-exceeded",
+        401 | 403 if mentions_quota => "quota-exceeded",
+        401 | 403 => "invalid",
+        400 if mentions_key => "invalid",
+        _ => "http-error",
+    }
+}
+
+/// Pull org/account details out of a successful probe response.
+fn provider_account_info(provider: &str, org_header: Option<String>, body: &serde_json::Value) -> ProviderAccountInfo {
+    let str_at = |v: &serde_json::Value, key: &str| {
+        v.get(key)
+            .and_then(|x| x.as_str())
+            .map(|s| s.to_string())
+            .filter(|s| !s.is_empty())
+    };
+    match provider {
+        "xai" | "grok" => ProviderAccountInfo {
+            organization: str_at(body, "team_id"),
+            name: str_at(body, "name"),
+            balance: None,
+        },
+        "moonshot" | "kimi" => ProviderAccountInfo {
+            organization: None,
+            name: None,
+            balance: body
+                .get("data")
+                .and_then(|d| d.get("available_balance"))
+                .and_then(|x| x.as_f64()),
+        },
+        _ => ProviderAccountInfo {
+            organization: org_header,
+            ..Default::default()
+        },
+    }
+}
+
+fn provider_key_validate_blocking(provider: &str, api_key: Option<String>) -> ProviderKeyValidation {
+    let mut out = ProviderKeyValidation {
+        provider: provider.to_string(),
+        env_var: None,
+        status: "missing".to_string(),
+        http_status: None,
+        message: None,
+        account: None,
+    };
+    let Some(env_vars) = provider_key_env_vars(provider) else {
+        out.status = "invalid".to_string();
+        out.message = Some(format!("unknown provider: {}", provider));
+        return out;
+    };
+
+    let key = match non_empty_trimmed(api_key) {
+        Some(k) => k,
+        None => {
+            let from_env = if provider == "openai" {
+                resolve_openai_bearer_for_gui().ok().map(|k| (env_vars[0], k))
+            } else {
+                env_vars.iter().find_map(|var| read_env_key(var).ok().map(|k| (*var, k)))
+            };
+            match from_env {
+                Some((var, k)) => {
+                    out.env_var = Some(var.to_string());
+                    k
+                }
+                None => {
+                    out.message = Some(format!("{} is not set", env_vars.join(" / ")));
+                    return out;
+                }
+            }
+        }
+    };
+    provider_key_probe(provider, &key, &mut out);
+    out
+}
+
+/// Call the provider with `key` and fill in status, HTTP code and account details. Also used
+/// to check the bearer token right after OpenAI OAuth login.
+fn provider_key_probe(provider: &str, key: &str, out: &mut ProviderKeyValidation) {
+    let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(15)).build();
+    // Cheapest authenticated endpoint per provider: a one-item model list, or an account lookup.
+    let (req, org_header) = match provider {
+        "openai" => {
+            let base = openai_oauth_config_from_env().api_base;
+            (
+                agent
+                    .get(&format!("{}/v1/models", base.trim_end_matches('/')))
+                    .set("Authorization", &format!("Bearer {}", key)),
+                Some("openai-organization"),
+            )
+        }
+        "anthropic" | "claude" => (
+            agent
+                .get("https://api.anthropic.com/v1/models?limit=1")
+                .set("x-api-key", key)
+                .set("anthropic-version", "2023-06-01"),
+            Some("anthropic-organization-id"),
+        ),
+        "xai" | "grok" => (
+            agent
+                .get("https://api.x.ai/v1/api-key")
+                .set("Authorization", &format!("Bearer {}", key)),
+            None,
+        ),
+        "moonshot" | "kimi" => {
+            let base = std::env::var("MOONSHOT_API_BASE").unwrap_or_else(|_| "https://api.moonshot.ai".to_string());
+            (
+                agent
+                    .get(&format!("{}/v1/users/me/balance", base.trim_end_matches('/')))
+                    .set("Authorization", &format!("Bearer {}", key)),
+                None,
+            )
+        }
+        _ => (
+            agent
+                .get("https://generativelanguage.googleapis.com/v1beta/models?pageSize=1")
+                .set("x-goog-api-key", key),
+            None,
+        ),
+    };
+
     match req.call() {
-        Ok(_) => Ok(()),
-        Err(ureq::Error::Status(code, r)) => {
-            let body = r.into_string().unwrap_or_default();
-            Err(format!(
-                "OpenAI bearer validation failed (status {}): {}",
-                code, body
-            ))
+        Ok(resp) => {
+            out.http_status = Some(resp.status());
+            let org = org_header.and_then(|h| resp.header(h)).map(|s| s.to_string());
+            let body: serde_json::Value = resp.into_json().unwrap_or(serde_json::Value::Null);
+            let account = provider_account_info(provider, org, &body);
+            out.status = "valid".to_string();
+            if body.get("api_key_blocked").and_then(|x| x.as_bool()) == Some(true)
+                || body.get("api_key_disabled").and_then(|x| x.as_bool()) == Some(true)
+            {
+                out.status = "invalid".to_string();
+                out.message = Some("API key is blocked or disabled".to_string());
+            } else if body.get("team_blocked").and_then(|x| x.as_bool()) == Some(true)
+                || account.balance.is_some_and(|b| b <= 0.0)
+            {
+                out.status = "quota-exceeded".to_string();
+                out.message = Some("account has no remaining credit".to_string());
+            }
+            out.account = Some(account);
+        }
+        Err(ureq::Error::Status(code, resp)) => {
+            let body = resp.into_string().unwrap_or_default();
+            out.http_status = Some(code);
+            out.status = classify_provider_http_error(code, &body).to_string();
+            out.message = Some(format!("status {}: {}", code, body.chars().take(300).collect::<String>()));
+        }
+        Err(e) => {
+            out.status = "network-error".to_string();
+            out.message = Some(e.to_string());
         }
-        Err(e) => Err(format!("OpenAI bearer validation request failed: {}", e)),
     }
 }
 
+/// Check a provider API key against the provider's cheapest authenticated endpoint.
+/// Uses `api_key` when given (e.g. before saving), else the key from the active env profile.
+#[tauri::command]
+pub async fn provider_key_validate(provider: String, api_key: Option<String>) -> Result<ProviderKeyValidation, String> {
+    let provider = provider.trim().to_lowercase();
+    tauri::async_runtime::spawn_blocking(move || provider_key_validate_blocking(&provider, api_key))
+        .await
+        .map_err(|e| format!("join error: {}", e))
+}
+
 fn is_missing_openai_model_read_scope_error(msg: &str) -> bool {
     let m = msg.to_lowercase();
     m.contains("api.model.read")
@@ -1385,11 +3587,13 @@ fn openai_login_start_blocking(no_browser: bool) -> Result<OpenAiLoginStartRespo
     let session = token_response_to_oauth_session(token_resp);
 
     let mut warning = None;
-    if let Err(e) = validate_openai_bearer_token(&session.access_token, &cfg.api_base) {
-        if is_missing_openai_model_read_scope_error(&e) {
+    let check = provider_key_validate_blocking("openai", Some(session.access_token.clone()));
+    if check.status != "valid" {
+        let msg = check.message.unwrap_or_default();
+        if is_missing_openai_model_read_scope_error(&msg) {
             warning = Some("OAuth login succeeded, but /v1/models scope check was denied (api.model.read). Snailer saved the token and will still prefer OAuth for GPT requests.".to_string());
         } else {
-            return Err(e);
+            return Err(format!("OpenAI bearer validation failed ({}): {}", check.status, msg));
         }
     }
 
@@ -1432,7 +3636,7 @@ pub async fn openai_login_start(no_browser: Option<bool>) -> Result<OpenAiLoginS
 /// Get the configured auth server address persisted by this GUI (does not include env overrides).
 #[tauri::command]
 pub async fn auth_addr_get() -> Result<Option<String>, String> {
-    Ok(read_gui_settings_auth_addr())
+    Ok(gui_settings().auth_addr)
 }
 
 /// Set (or clear) the auth server address persisted by this GUI.
@@ -1441,11 +3645,11 @@ pub async fn auth_addr_set(addr: Option<String>) -> Result<Option<String>, Strin
     match addr.as_deref() {
         Some(v) if !v.trim().is_empty() => {
             let normalized = normalize_legacy_auth_addr(v);
-            write_gui_settings_auth_addr(Some(&normalized))?;
+            update_gui_settings(|s| s.auth_addr = Some(normalized.clone()))?;
             Ok(Some(normalized))
         }
         _ => {
-            write_gui_settings_auth_addr(None)?;
+            update_gui_settings(|s| s.auth_addr = None)?;
             Ok(None)
         }
     }
@@ -1576,89 +3780,429 @@ pub async fn attachment_save_image_from_path(req: AttachmentSavePathRequest) ->
     .map_err(|e| format!("save task failed: {}", e))?
 }
 
-/// Ensure the Snailer npm CLI (`@snailer-team/snailer`) is installed for this user.
+/// npm arguments for installing the CLI, and the install source they represent.
+///
+/// With an offline bundle the install runs `--offline` against the packed `.tgz` and/or the
+/// bundled npm cache, so it never reaches the registry.
+fn npm_install_args(version_spec: &str, bundle: Option<&OfflineBundle>) -> (Vec<String>, &'static str) {
+    let mut args: Vec<String> = ["install", "--no-fund", "--no-audit"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let registry_spec = format!("@snailer-team/snailer@{}", version_spec);
+    let Some(bundle) = bundle.filter(|b| b.cli_tgz.is_some() || b.npm_cache.is_some()) else {
+        args.push(registry_spec);
+        return (args, "registry");
+    };
+    args.push("--offline".to_string());
+    if let Some(cache) = bundle.npm_cache.as_deref() {
+        args.push("--cache".to_string());
+        args.push(cache.to_string_lossy().to_string());
+    }
+    match bundle.cli_tgz.as_deref() {
+        Some(tgz) => {
+            args.push(tgz.to_string_lossy().to_string());
+            (args, "offline-tgz")
+        }
+        None => {
+            args.push(registry_spec);
+            (args, "offline-cache")
+        }
+    }
+}
+
+fn cli_install_source_path(prefix: &Path) -> PathBuf {
+    prefix.join(".install-source")
+}
+
+fn write_cli_install_source(prefix: &Path, source: &str) {
+    let path = cli_install_source_path(prefix);
+    if let Err(e) = crate::persist::write(&path, source, crate::persist::Policy::TEXT_NO_BACKUP) {
+        log::warn!("failed to record CLI install source: {}", e);
+    }
+}
+
+fn read_cli_install_source(prefix: &Path) -> Option<String> {
+    non_empty_trimmed(std::fs::read_to_string(cli_install_source_path(prefix)).ok())
+}
+
+const KEYCHAIN_NPM_TOKEN_KEY: &str = "npm-registry-token";
+
+fn npm_token_keychain_entry() -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_NPM_TOKEN_KEY)
+        .map_err(|e| format!("keychain init failed: {}", e))
+}
+
+fn npm_token_keychain_get() -> Option<String> {
+    npm_token_keychain_entry()
+        .ok()
+        .and_then(|entry| entry.get_password().ok())
+        .and_then(|t| non_empty_trimmed(Some(t)))
+}
+
+/// npm registry settings for CLI installs. The auth token lives in the keychain only.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NpmRegistryConfig {
+    pub registry: Option<String>,
+    /// Registry for the `@snailer-team` scope only.
+    pub scope_registry: Option<String>,
+    pub has_auth_token: bool,
+}
+
+fn read_npm_registry_config() -> NpmRegistryConfig {
+    let settings = gui_settings();
+    NpmRegistryConfig {
+        registry: settings.npm_registry,
+        scope_registry: settings.npm_scope_registry,
+        has_auth_token: npm_token_keychain_get().is_some(),
+    }
+}
+
+fn normalize_registry_url(raw: &str) -> Result<String, String> {
+    let url = Url::parse(raw.trim()).map_err(|e| format!("invalid registry URL {}: {}", raw.trim(), e))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(format!("registry URL must be http(s): {}", raw.trim()));
+    }
+    let mut text = url.to_string();
+    if !text.ends_with('/') {
+        text.push('/');
+    }
+    Ok(text)
+}
+
+/// npm's "nerf dart" key for per-registry auth: `//host[:port]/path/`.
+fn npm_nerf_dart(registry: &str) -> Option<String> {
+    let url = Url::parse(registry).ok()?;
+    let host = url.host_str()?;
+    let port = url.port().map(|p| format!(":{}", p)).unwrap_or_default();
+    let mut path = url.path().to_string();
+    if !path.ends_with('/') {
+        path.push('/');
+    }
+    Some(format!("//{}{}{}", host, port, path))
+}
+
+/// Env var the npm subprocess reads the registry token from; `.npmrc` only references it.
+const NPM_TOKEN_ENV: &str = "SNAILER_NPM_REGISTRY_TOKEN";
+
+/// Render the GUI `.npmrc`. With `with_token`, registries authenticate with
+/// `${SNAILER_NPM_REGISTRY_TOKEN}`, which npm expands from the child's environment, so the token
+/// itself never touches disk.
+fn render_npmrc(registry: Option<&str>, scope_registry: Option<&str>, with_token: bool) -> String {
+    let mut out = String::new();
+    if let Some(r) = registry {
+        out.push_str(&format!("registry={}\n", r));
+    }
+    if let Some(r) = scope_registry {
+        out.push_str(&format!("@snailer-team:registry={}\n", r));
+    }
+    if with_token {
+        let mut darts: Vec<String> = [registry, scope_registry]
+            .into_iter()
+            .flatten()
+            .filter_map(npm_nerf_dart)
+            .collect();
+        darts.dedup();
+        for dart in darts {
+            out.push_str(&format!("{}:_authToken=${{{}}}\n", dart, NPM_TOKEN_ENV));
+        }
+    }
+    out
+}
+
+fn snailer_cli_npmrc_path() -> PathBuf {
+    snailer_cli_prefix_dir().join(".npmrc")
+}
+
+/// Point an npm subprocess at the GUI's own `.npmrc` when a registry is configured.
 ///
-/// Installs into `~/.snailer/npm_cli` (not global) and returns the resolved CLI binary path.
+/// The file is regenerated on every call and passed via `npm_config_userconfig`, so the user's
+/// own `~/.npmrc` is neither read nor modified. The keychain token goes only into the child's
+/// environment.
+fn apply_npm_registry_config(cmd: &mut std::process::Command) -> Result<(), String> {
+    let GuiSettings {
+        npm_registry: registry,
+        npm_scope_registry: scope_registry,
+        ..
+    } = gui_settings();
+    let path = snailer_cli_npmrc_path();
+    if registry.is_none() && scope_registry.is_none() {
+        let _ = std::fs::remove_file(&path);
+        return Ok(());
+    }
+    let token = npm_token_keychain_get();
+    let text = render_npmrc(registry.as_deref(), scope_registry.as_deref(), token.is_some());
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("mkdir failed: {}", e))?;
+    }
+    crate::persist::write(&path, &text, crate::persist::Policy::TEXT_NO_BACKUP)
+        .map_err(|e| format!("write .npmrc failed: {}", e))?;
+    chmod_600(&path);
+    cmd.env("npm_config_userconfig", &path);
+    if let Some(token) = token {
+        cmd.env(NPM_TOKEN_ENV, token);
+    }
+    Ok(())
+}
+
+/// Get the npm registry settings used for CLI installs.
 #[tauri::command]
-pub async fn snailer_cli_ensure_installed() -> Result<String, String> {
-    tauri::async_runtime::spawn_blocking(|| {
-        let (npm_cmd, maybe_node_bin) = resolve_npm_command()?;
+pub async fn npm_registry_get() -> Result<NpmRegistryConfig, String> {
+    tauri::async_runtime::spawn_blocking(read_npm_registry_config)
+        .await
+        .map_err(|e| format!("Registry task failed: {}", e))
+}
+
+/// Set (or clear, with empty values) the registry and `@snailer-team` scope registry.
+#[tauri::command]
+pub async fn npm_registry_set(
+    registry: Option<String>,
+    scope_registry: Option<String>,
+) -> Result<NpmRegistryConfig, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let registry = non_empty_trimmed(registry).map(|r| normalize_registry_url(&r)).transpose()?;
+        let scope_registry = non_empty_trimmed(scope_registry)
+            .map(|r| normalize_registry_url(&r))
+            .transpose()?;
+        update_gui_settings(|s| {
+            s.npm_registry = registry;
+            s.npm_scope_registry = scope_registry;
+        })?;
+        Ok(read_npm_registry_config())
+    })
+    .await
+    .map_err(|e| format!("Registry task failed: {}", e))?
+}
+
+/// Store (or clear, with an empty value) the registry auth token in the keychain.
+#[tauri::command]
+pub async fn npm_registry_token_set(token: Option<String>) -> Result<NpmRegistryConfig, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let entry = npm_token_keychain_entry()?;
+        match non_empty_trimmed(token) {
+            Some(t) => entry
+                .set_password(&t)
+                .map_err(|e| format!("keychain write failed: {}", e))?,
+            None => {
+                let _ = entry.delete_password();
+            }
+        }
+        Ok(read_npm_registry_config())
+    })
+    .await
+    .map_err(|e| format!("Registry task failed: {}", e))?
+}
 
-        if snailer_cli_health_ok(Path::new("snailer"), None).unwrap_or(false) {
-            return Ok("snailer".to_string());
+/// Get the offline install bundle directory persisted by this GUI (does not include env overrides).
+#[tauri::command]
+pub async fn offline_bundle_get() -> Result<Option<String>, String> {
+    Ok(gui_settings().offline_bundle_dir)
+}
+
+/// Set (or clear) the offline install bundle directory.
+#[tauri::command]
+pub async fn offline_bundle_set(dir: Option<String>) -> Result<Option<String>, String> {
+    match non_empty_trimmed(dir) {
+        Some(v) => {
+            if !Path::new(&v).is_dir() {
+                return Err(format!("offline bundle is not a directory: {}", v));
+            }
+            update_gui_settings(|s| s.offline_bundle_dir = Some(v.clone()))?;
+            Ok(Some(v))
+        }
+        None => {
+            update_gui_settings(|s| s.offline_bundle_dir = None)?;
+            Ok(None)
         }
+    }
+}
+
+/// Side-by-side CLI installs live in `~/.snailer/npm_cli/versions/<version>`.
+fn snailer_cli_versions_dir() -> PathBuf {
+    snailer_cli_prefix_dir().join("versions")
+}
+
+fn snailer_cli_versions_state_path() -> PathBuf {
+    snailer_cli_prefix_dir().join("versions.json")
+}
+
+/// Which side-by-side version is active, plus what to roll back to.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct CliVersionsState {
+    current: Option<String>,
+    previous: Option<String>,
+    last_known_good: Option<String>,
+    channel: Option<String>,
+}
+
+fn read_cli_versions_state() -> CliVersionsState {
+    crate::persist::read_json(&snailer_cli_versions_state_path()).unwrap_or_default()
+}
+
+/// Written atomically, so the "current" switch survives the app dying mid-update.
+fn write_cli_versions_state(state: &CliVersionsState) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(state).map_err(|e| format!("serialize failed: {}", e))?;
+    crate::persist::write(&snailer_cli_versions_state_path(), &text, crate::persist::Policy::JSON)
+}
 
-        let prefix = snailer_cli_prefix_dir();
+/// The prefix the CLI runs from: the active side-by-side version, else the legacy
+/// single install directly under `~/.snailer/npm_cli`.
+fn snailer_cli_active_prefix() -> PathBuf {
+    if let Some(current) = read_cli_versions_state().current {
+        let prefix = snailer_cli_versions_dir().join(current);
         if snailer_cli_is_installed(&prefix) {
-            let local_bin = snailer_cli_bin_path(&prefix);
-            if snailer_cli_health_ok(&local_bin, maybe_node_bin.as_deref()).unwrap_or(false) {
-                return Ok(local_bin.to_string_lossy().to_string());
+            return prefix;
+        }
+    }
+    snailer_cli_prefix_dir()
+}
+
+fn read_installed_cli_version(prefix: &Path) -> Option<String> {
+    let pkg = prefix
+        .join("node_modules")
+        .join("@snailer-team")
+        .join("snailer")
+        .join("package.json");
+    let v: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(pkg).ok()?).ok()?;
+    v.get("version")?.as_str().and_then(|s| semver::Version::parse(s).ok()).map(|v| v.to_string())
+}
+
+/// Run a command to completion, forwarding each stdout/stderr line as install progress.
+fn run_cmd_streaming(
+    mut cmd: std::process::Command,
+    reporter: &InstallReporter,
+    phase: &str,
+) -> std::io::Result<std::process::Output> {
+    cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = cmd.spawn()?;
+    let forward = |pipe: Option<Box<dyn Read + Send>>| {
+        let reporter = reporter.clone();
+        let phase = phase.to_string();
+        std::thread::spawn(move || {
+            let mut collected = Vec::new();
+            let Some(pipe) = pipe else {
+                return collected;
+            };
+            let mut reader = std::io::BufReader::new(pipe);
+            let mut line = Vec::new();
+            while matches!(reader.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+                collected.extend_from_slice(&line);
+                let text = String::from_utf8_lossy(&line).trim_end().to_string();
+                if !text.is_empty() {
+                    reporter.line(&phase, text);
+                }
+                line.clear();
             }
-        }
-
-        std::fs::create_dir_all(&prefix).map_err(|e| format!("Failed to create install dir: {}", e))?;
+            collected
+        })
+    };
+    let stdout = forward(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let stderr = forward(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let status = child.wait()?;
+    Ok(std::process::Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
 
-        // Ensure package.json exists so npm installs into this directory
-        let pkg_json = prefix.join("package.json");
-        if !pkg_json.is_file() {
-            std::fs::write(&pkg_json, "{\"private\":true}")
-                .map_err(|e| format!("Failed to create package.json: {}", e))?;
+/// `npm install` the CLI into a staging directory, then move it to `versions/<version>`.
+/// Returns the installed version. Does not change which version is active.
+fn install_cli_version(
+    npm_cmd: &str,
+    node_bin: Option<&Path>,
+    version_spec: &str,
+    bundle: Option<&OfflineBundle>,
+    reporter: &InstallReporter,
+) -> Result<String, String> {
+    let versions = snailer_cli_versions_dir();
+    let staging = versions.join(format!(".staging-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&staging).map_err(|e| format!("Failed to create install dir: {}", e))?;
+    let result = install_cli_into(npm_cmd, node_bin, version_spec, bundle, &staging, reporter);
+    let (version, install_source) = match result {
+        Ok(v) => v,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(e);
         }
-
-        let mut npm_check = std::process::Command::new(&npm_cmd);
-        if let Some(bin) = maybe_node_bin.as_ref() {
-            npm_check.env("PATH", prepend_path(bin));
+    };
+    write_cli_install_source(&staging, install_source);
+
+    let target = versions.join(&version);
+    if snailer_cli_is_installed(&target) {
+        // Already have this version side-by-side (and it may be in use); keep it.
+        let _ = std::fs::remove_dir_all(&staging);
+        return Ok(version);
+    }
+    if target.exists() {
+        let _ = std::fs::remove_dir_all(&target);
+    }
+    std::fs::rename(&staging, &target).map_err(|e| {
+        let _ = std::fs::remove_dir_all(&staging);
+        format!("Failed to move CLI {} into place: {}", version, e)
+    })?;
+    Ok(version)
+}
+
+fn install_cli_into(
+    npm_cmd: &str,
+    node_bin: Option<&Path>,
+    version_spec: &str,
+    bundle: Option<&OfflineBundle>,
+    prefix: &Path,
+    reporter: &InstallReporter,
+) -> Result<(String, &'static str), String> {
+    // Ensure package.json exists so npm installs into this directory
+    std::fs::write(prefix.join("package.json"), "{\"private\":true}")
+        .map_err(|e| format!("Failed to create package.json: {}", e))?;
+
+    let mut out_cmd = std::process::Command::new(npm_cmd);
+    if let Some(bin) = node_bin {
+        out_cmd.env("PATH", prepend_path(bin));
+    }
+    // Offline installs must not depend on a registry being reachable.
+    if bundle.is_none() {
+        apply_npm_registry_config(&mut out_cmd)?;
+    }
+    let (install_args, install_source) = npm_install_args(version_spec, bundle);
+    reporter.phase("npm-install", format!("npm {}", install_args.join(" ")));
+    out_cmd
+        .current_dir(prefix)
+        .env("npm_config_update_notifier", "false")
+        .args(&install_args);
+    let out = run_cmd_streaming(out_cmd, reporter, "npm-install")
+        .map_err(|e| format!("Failed to run npm install: {}", e))?;
+
+    if !out.status.success() {
+        let mut msg = String::new();
+        if !out.stdout.is_empty() {
+            msg.push_str(&String::from_utf8_lossy(&out.stdout));
         }
-        let npm_check = npm_check
-            .arg("--version")
-            .output()
-            .map_err(|_| "npm not found and auto-install failed. Please install Node.js (which includes npm) and try again.".to_string())?;
-        if !npm_check.status.success() {
-            return Err("npm is installed but not working. Please reinstall Node.js/npm and try again.".to_string());
-        }
-
-        let mut out_cmd = std::process::Command::new(&npm_cmd);
-        if let Some(bin) = maybe_node_bin.as_ref() {
-            out_cmd.env("PATH", prepend_path(bin));
-        }
-        let out = out_cmd
-            .current_dir(&prefix)
-            .env("npm_config_update_notifier", "false")
-            .args([
-                "install",
-                "--no-fund",
-                "--no-audit",
-                "@snailer-team/snailer@latest",
-            ])
-            .output()
-            .map_err(|e| format!("Failed to run npm install: {}", e))?;
-
-        if !out.status.success() {
-            let mut msg = String::new();
-            if !out.stdout.is_empty() {
-                msg.push_str(&String::from_utf8_lossy(&out.stdout));
-            }
-            if !out.stderr.is_empty() {
-                if !msg.is_empty() {
-                    msg.push('\n');
-                }
-                msg.push_str(&String::from_utf8_lossy(&out.stderr));
+        if !out.stderr.is_empty() {
+            if !msg.is_empty() {
+                msg.push('\n');
             }
-            let trimmed = msg.trim();
-            let clipped = if trimmed.len() > 4000 {
-                format!("{}...", &trimmed[..4000])
-            } else {
-                trimmed.to_string()
-            };
-            return Err(if clipped.is_empty() {
-                "npm install failed (no output).".to_string()
-            } else {
-                format!("npm install failed:\n{}", clipped)
-            });
+            msg.push_str(&String::from_utf8_lossy(&out.stderr));
         }
+        let trimmed = msg.trim();
+        let clipped = if trimmed.len() > 4000 {
+            format!("{}...", &trimmed[..4000])
+        } else {
+            trimmed.to_string()
+        };
+        return Err(if clipped.is_empty() {
+            "npm install failed (no output).".to_string()
+        } else {
+            format!("npm install failed:\n{}", clipped)
+        });
+    }
 
-        if !snailer_cli_is_installed(&prefix) {
-            let expected_bin = snailer_cli_bin_path(&prefix);
+    let version = match (snailer_cli_is_installed(prefix), read_installed_cli_version(prefix)) {
+        (true, Some(v)) => v,
+        _ => {
+            let expected_bin = snailer_cli_bin_path(prefix);
             let expected_pkg = prefix.join("node_modules").join("@snailer-team").join("snailer").join("package.json");
             let mut diag = format!(
                 "npm install reported success, but Snailer CLI was not found after install.\n\
@@ -1680,21 +4224,404 @@ pub async fn snailer_cli_ensure_installed() -> Result<String, String> {
             }
             return Err(diag);
         }
+    };
+    Ok((version, install_source))
+}
 
-        let final_bin = snailer_cli_bin_path(&prefix);
-        if !snailer_cli_health_ok(&final_bin, maybe_node_bin.as_deref()).unwrap_or(false) {
+/// Health-check `versions/<version>` and, if it passes, make it current.
+fn activate_cli_version(version: &str, node_bin: Option<&Path>, reporter: &InstallReporter) -> Result<String, String> {
+    let bin = snailer_cli_bin_path(&snailer_cli_versions_dir().join(version));
+    reporter.phase("health-check", format!("Checking Snailer CLI {}", version));
+    match snailer_cli_check(&bin, node_bin)? {
+        CliHealth::Ok(_) => {}
+        CliHealth::Incompatible(v) => {
+            return Err(SnailerCliCompat::from_health(&CliHealth::Incompatible(v)).error_message());
+        }
+        CliHealth::Broken => {
             return Err(format!(
                 "Snailer CLI installed but failed health check (version/help). bin={}",
-                final_bin.display()
+                bin.display()
             ));
         }
+    }
+    let mut state = read_cli_versions_state();
+    switch_cli_version(&mut state, version);
+    write_cli_versions_state(&state)?;
+    prune_cli_versions(&state);
+    Ok(bin.to_string_lossy().to_string())
+}
+
+fn switch_cli_version(state: &mut CliVersionsState, version: &str) {
+    if state.current.as_deref() != Some(version) {
+        state.previous = state.current.take();
+        state.current = Some(version.to_string());
+    }
+    state.last_known_good = Some(version.to_string());
+}
+
+/// Versions worth switching back to, best first. If the current version is still
+/// healthy the user asked for a rollback, so prefer the one before it.
+fn cli_rollback_candidates(state: &CliVersionsState, current_healthy: bool) -> Vec<String> {
+    let ordered = if current_healthy {
+        [state.previous.as_ref(), state.last_known_good.as_ref()]
+    } else {
+        [state.last_known_good.as_ref(), state.previous.as_ref()]
+    };
+    let mut out: Vec<String> = Vec::new();
+    for v in ordered.into_iter().flatten() {
+        if Some(v) != state.current.as_ref() && !out.contains(v) {
+            out.push(v.clone());
+        }
+    }
+    out
+}
+
+/// Switch to the first healthy rollback candidate; returns its binary path.
+fn cli_rollback_to_healthy(node_bin: Option<&Path>, current_healthy: bool) -> Result<Option<String>, String> {
+    let mut state = read_cli_versions_state();
+    for candidate in cli_rollback_candidates(&state, current_healthy) {
+        let prefix = snailer_cli_versions_dir().join(&candidate);
+        if !snailer_cli_is_installed(&prefix) {
+            continue;
+        }
+        let bin = snailer_cli_bin_path(&prefix);
+        if snailer_cli_health_ok(&bin, node_bin).unwrap_or(false) {
+            switch_cli_version(&mut state, &candidate);
+            write_cli_versions_state(&state)?;
+            return Ok(Some(bin.to_string_lossy().to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// Side-by-side versions that running engines were started from.
+fn cli_versions_in_use() -> Vec<String> {
+    let versions_dir = snailer_cli_versions_dir();
+    let mut guard = engines().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    guard
+        .values_mut()
+        .filter_map(|st| {
+            if !st.is_running() {
+                return None;
+            }
+            let rel = Path::new(&st.cli_bin).strip_prefix(&versions_dir).ok()?;
+            rel.components().next().map(|c| c.as_os_str().to_string_lossy().to_string())
+        })
+        .collect()
+}
+
+/// Remove side-by-side versions that are neither current, previous, last known-good nor in
+/// use by a running engine.
+fn prune_cli_versions(state: &CliVersionsState) {
+    let in_use = cli_versions_in_use();
+    let keep: Vec<&str> = [&state.current, &state.previous, &state.last_known_good]
+        .into_iter()
+        .filter_map(|v| v.as_deref())
+        .chain(in_use.iter().map(String::as_str))
+        .collect();
+    let Ok(rd) = std::fs::read_dir(snailer_cli_versions_dir()) else {
+        return;
+    };
+    for entry in rd.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with(".staging-") || keep.contains(&name.as_str()) {
+            continue;
+        }
+        let _ = std::fs::remove_dir_all(entry.path());
+    }
+}
+
+const CLI_CHANNELS: &[&str] = &["stable", "beta", "pinned"];
+
+fn resolve_cli_channel(channel: Option<String>, state: &CliVersionsState) -> Result<String, String> {
+    let channel = non_empty_trimmed(channel)
+        .or_else(|| state.channel.clone())
+        .unwrap_or_else(|| "pinned".to_string())
+        .to_ascii_lowercase();
+    if CLI_CHANNELS.contains(&channel.as_str()) {
+        Ok(channel)
+    } else {
+        Err(format!("unknown update channel: {} (expected stable, beta or pinned)", channel))
+    }
+}
+
+/// Pick the version a channel points at: `stable` and `beta` follow npm dist-tags,
+/// `pinned` is the newest release inside the supported version range.
+fn cli_channel_target(
+    channel: &str,
+    versions: &[semver::Version],
+    dist_tags: &HashMap<String, String>,
+    req: &semver::VersionReq,
+) -> Option<semver::Version> {
+    let tag = |name: &str| dist_tags.get(name).and_then(|v| semver::Version::parse(v).ok());
+    match channel {
+        "stable" => tag("latest"),
+        "beta" => tag("beta").or_else(|| tag("latest")),
+        _ => versions
+            .iter()
+            .filter(|v| v.pre.is_empty() && req.matches(v))
+            .max()
+            .cloned(),
+    }
+}
+
+/// Published versions and dist-tags from the registry.
+fn npm_view_cli_versions(
+    npm_cmd: &str,
+    node_bin: Option<&Path>,
+) -> Result<(Vec<semver::Version>, HashMap<String, String>), String> {
+    let mut cmd = std::process::Command::new(npm_cmd);
+    if let Some(bin) = node_bin {
+        cmd.env("PATH", prepend_path(bin));
+    }
+    apply_npm_registry_config(&mut cmd)?;
+    let out = cmd
+        .env("npm_config_update_notifier", "false")
+        .args(["view", "@snailer-team/snailer", "versions", "dist-tags", "--json"])
+        .output()
+        .map_err(|e| format!("Failed to run npm view: {}", e))?;
+    if !out.status.success() {
+        return Err(format!(
+            "npm view failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    let v: serde_json::Value =
+        serde_json::from_slice(&out.stdout).map_err(|e| format!("npm view returned invalid JSON: {}", e))?;
+    let versions = v
+        .get("versions")
+        .and_then(|x| x.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|x| x.as_str().and_then(|s| semver::Version::parse(s).ok()))
+                .collect()
+        })
+        .unwrap_or_default();
+    let dist_tags = v
+        .get("dist-tags")
+        .and_then(|x| x.as_object())
+        .map(|obj| {
+            obj.iter()
+                .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok((versions, dist_tags))
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnailerCliUpdateInfo {
+    pub channel: String,
+    pub current: Option<String>,
+    pub target: Option<String>,
+    pub update_available: bool,
+    /// Whether `target` is inside the version range this GUI supports.
+    pub target_compatible: bool,
+    pub previous: Option<String>,
+    pub last_known_good: Option<String>,
+}
+
+/// Newer versions are updates. An older target only counts when the user is switching channel
+/// (e.g. beta -> stable), never as a side effect of a dist-tag moving backwards.
+fn cli_update_available(current: Option<&semver::Version>, target: Option<&semver::Version>, switching_channel: bool) -> bool {
+    match (current, target) {
+        (Some(c), Some(t)) => t > c || (switching_channel && t != c),
+        (None, Some(_)) => true,
+        _ => false,
+    }
+}
+
+/// A healthy global `snailer` is used only until a managed side-by-side version has been
+/// activated; after that the managed `current` wins, so updates and rollbacks take effect.
+fn global_cli_health(state: &CliVersionsState) -> CliHealth {
+    if state.current.is_some() {
+        return CliHealth::Broken;
+    }
+    snailer_cli_check(Path::new("snailer"), None).unwrap_or(CliHealth::Broken)
+}
+
+fn cli_update_info(npm_cmd: &str, node_bin: Option<&Path>, channel: Option<String>) -> Result<SnailerCliUpdateInfo, String> {
+    let state = read_cli_versions_state();
+    let channel = resolve_cli_channel(channel, &state)?;
+    let switching_channel = state.channel.as_deref().unwrap_or("pinned") != channel;
+    let req = snailer_cli_version_req()?;
+    let (versions, dist_tags) = npm_view_cli_versions(npm_cmd, node_bin)?;
+    let target = cli_channel_target(&channel, &versions, &dist_tags, &req);
+    let current = match global_cli_health(&state) {
+        CliHealth::Ok(v) => Some(v),
+        _ => read_installed_cli_version(&snailer_cli_active_prefix()).and_then(|v| semver::Version::parse(&v).ok()),
+    };
+    let update_available = cli_update_available(current.as_ref(), target.as_ref(), switching_channel);
+    Ok(SnailerCliUpdateInfo {
+        channel,
+        current: current.map(|v| v.to_string()),
+        target_compatible: target.as_ref().map(|t| req.matches(t)).unwrap_or(false),
+        target: target.map(|v| v.to_string()),
+        update_available,
+        previous: state.previous,
+        last_known_good: state.last_known_good,
+    })
+}
+
+/// Check the registry for the version the given channel (default: the saved one, else `pinned`) points at.
+#[tauri::command]
+pub async fn snailer_cli_check_update(
+    app: tauri::AppHandle,
+    channel: Option<String>,
+) -> Result<SnailerCliUpdateInfo, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let reporter = InstallReporter::new(app);
+        let result = resolve_npm_command(&reporter)
+            .and_then(|(npm_cmd, node_bin)| cli_update_info(&npm_cmd, node_bin.as_deref(), channel));
+        reporter.finish(&result, "Update check complete");
+        result
+    })
+    .await
+    .map_err(|e| format!("Update check task failed: {}", e))?
+}
+
+/// Install the channel's target side-by-side and switch to it once it passes the health check.
+///
+/// The previously active version is kept for `snailer_cli_rollback`. Running engines keep
+/// using the version they were started with until restarted.
+#[tauri::command]
+pub async fn snailer_cli_update(
+    app: tauri::AppHandle,
+    channel: Option<String>,
+) -> Result<SnailerCliUpdateInfo, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let reporter = InstallReporter::new(app);
+        let result = cli_update(&reporter, channel);
+        reporter.finish(&result, "Snailer CLI is up to date");
+        result
+    })
+    .await
+    .map_err(|e| format!("Update task failed: {}", e))?
+}
 
-        Ok(final_bin.to_string_lossy().to_string())
+fn cli_update(reporter: &InstallReporter, channel: Option<String>) -> Result<SnailerCliUpdateInfo, String> {
+    let (npm_cmd, node_bin) = resolve_npm_command(reporter)?;
+    let info = cli_update_info(&npm_cmd, node_bin.as_deref(), channel)?;
+    let Some(target) = info.target.clone() else {
+        return Err(format!("no version published for channel {}", info.channel));
+    };
+    if !info.update_available {
+        return Ok(info);
+    }
+    if !info.target_compatible {
+        return Err(format!(
+            "Snailer CLI {} on the {} channel is outside the range this app supports ({}); update the app first",
+            target,
+            info.channel,
+            snailer_cli_version_req_text()
+        ));
+    }
+    if let CliHealth::Ok(global) = global_cli_health(&read_cli_versions_state()) {
+        return Err(format!(
+            "the global Snailer CLI {} on PATH takes precedence over app-managed versions; update it with npm (npm install -g @snailer-team/snailer) instead",
+            global
+        ));
+    }
+
+    let version = install_cli_version(&npm_cmd, node_bin.as_deref(), &target, None, reporter)?;
+    if let Err(e) = activate_cli_version(&version, node_bin.as_deref(), reporter) {
+        let state = read_cli_versions_state();
+        prune_cli_versions(&state);
+        return Err(e);
+    }
+    let mut state = read_cli_versions_state();
+    state.channel = Some(info.channel.clone());
+    write_cli_versions_state(&state)?;
+    Ok(SnailerCliUpdateInfo {
+        current: state.current,
+        update_available: false,
+        previous: state.previous,
+        last_known_good: state.last_known_good,
+        ..info
+    })
+}
+
+/// Switch back to the previous CLI version, or to the last known-good one if the current
+/// version fails its health check. Returns the CLI binary now in use.
+#[tauri::command]
+pub async fn snailer_cli_rollback() -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let node_bin = snailer_node_current_bin_dir();
+        let node_bin = node_bin.is_dir().then_some(node_bin);
+        let current_healthy = snailer_cli_health_ok(
+            &snailer_cli_bin_path(&snailer_cli_active_prefix()),
+            node_bin.as_deref(),
+        )
+        .unwrap_or(false);
+        cli_rollback_to_healthy(node_bin.as_deref(), current_healthy)?
+            .ok_or_else(|| "no healthy previous Snailer CLI version to roll back to".to_string())
+    })
+    .await
+    .map_err(|e| format!("Rollback task failed: {}", e))?
+}
+
+/// Ensure the Snailer npm CLI (`@snailer-team/snailer`) is installed for this user.
+///
+/// Installs side-by-side under `~/.snailer/npm_cli/versions` (not global) and returns the
+/// resolved CLI binary path.
+#[tauri::command]
+pub async fn snailer_cli_ensure_installed(app: tauri::AppHandle) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let reporter = InstallReporter::new(app);
+        let result = ensure_cli_installed(&reporter);
+        reporter.finish(&result, "Snailer CLI is ready");
+        result
     })
     .await
     .map_err(|e| format!("Install task failed: {}", e))?
 }
 
+fn ensure_cli_installed(reporter: &InstallReporter) -> Result<String, String> {
+    let (npm_cmd, maybe_node_bin) = resolve_npm_command(reporter)?;
+    let version_req = snailer_cli_version_req()?;
+
+    // A global `snailer` is only used when it satisfies the pinned range and no managed
+    // version has been activated.
+    if let CliHealth::Ok(_) = global_cli_health(&read_cli_versions_state()) {
+        return Ok("snailer".to_string());
+    }
+
+    let prefix = snailer_cli_active_prefix();
+    if snailer_cli_is_installed(&prefix) {
+        let local_bin = snailer_cli_bin_path(&prefix);
+        if snailer_cli_health_ok(&local_bin, maybe_node_bin.as_deref()).unwrap_or(false) {
+            return Ok(local_bin.to_string_lossy().to_string());
+        }
+        // A broken active version falls back to the last known-good one before reinstalling.
+        if let Some(bin) = cli_rollback_to_healthy(maybe_node_bin.as_deref(), false)? {
+            return Ok(bin);
+        }
+    }
+
+    let mut npm_check = std::process::Command::new(&npm_cmd);
+    if let Some(bin) = maybe_node_bin.as_ref() {
+        npm_check.env("PATH", prepend_path(bin));
+    }
+    let npm_check = npm_check
+        .arg("--version")
+        .output()
+        .map_err(|_| "npm not found and auto-install failed. Please install Node.js (which includes npm) and try again.".to_string())?;
+    if !npm_check.status.success() {
+        return Err("npm is installed but not working. Please reinstall Node.js/npm and try again.".to_string());
+    }
+
+    let bundle = offline_bundle();
+    let version = install_cli_version(
+        &npm_cmd,
+        maybe_node_bin.as_deref(),
+        &npm_version_spec(&version_req.to_string()),
+        bundle.as_ref(),
+        reporter,
+    )?;
+    activate_cli_version(&version, maybe_node_bin.as_deref(), reporter)
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SnailerCliStatus {
@@ -1704,6 +4631,10 @@ pub struct SnailerCliStatus {
     pub using_bundled_node: bool,
     pub bundled_node_path: Option<String>,
     pub prefix_dir: String,
+    pub compat: SnailerCliCompat,
+    /// How the resolved CLI got there: `global`, `registry`, `offline-tgz` or `offline-cache`.
+    pub install_source: Option<String>,
+    pub offline_bundle_dir: Option<String>,
 }
 
 #[tauri::command]
@@ -1736,11 +4667,13 @@ pub async fn snailer_cli_status() -> Result<SnailerCliStatus, String> {
         let npm_available = system_npm_ok || bundled_ok;
         let using_bundled_node = !system_npm_ok && bundled_ok;
 
-        let prefix = snailer_cli_prefix_dir();
+        let prefix = snailer_cli_active_prefix();
         let prefix_dir = prefix.to_string_lossy().to_string();
+        let offline_bundle_dir = offline_bundle_dir().map(|p| p.to_string_lossy().to_string());
 
-        // Installed CLI resolution order: global `snailer` then local prefix.
-        if snailer_cli_health_ok(Path::new("snailer"), None).unwrap_or(false) {
+        // Same resolution order as `ensure_cli_installed`.
+        let global_health = global_cli_health(&read_cli_versions_state());
+        if let CliHealth::Ok(_) = global_health {
             return Ok(SnailerCliStatus {
                 installed: true,
                 cli_path: Some("snailer".to_string()),
@@ -1752,184 +4685,925 @@ pub async fn snailer_cli_status() -> Result<SnailerCliStatus, String> {
                     None
                 },
                 prefix_dir,
+                compat: SnailerCliCompat::from_health(&global_health),
+                install_source: Some("global".to_string()),
+                offline_bundle_dir,
             });
         }
 
-        if snailer_cli_is_installed(&prefix) {
-            let local_bin = snailer_cli_bin_path(&prefix);
-            if snailer_cli_health_ok(&local_bin, Some(&bundled_bin)).unwrap_or(false) {
-                return Ok(SnailerCliStatus {
-                    installed: true,
-                    cli_path: Some(local_bin.to_string_lossy().to_string()),
-                    npm_available,
-                    using_bundled_node,
-                    bundled_node_path: if bundled_ok {
-                        Some(bundled_bin.to_string_lossy().to_string())
-                    } else {
-                        None
-                    },
-                    prefix_dir,
-                });
+        let mut local_health = CliHealth::Broken;
+        if snailer_cli_is_installed(&prefix) {
+            let local_bin = snailer_cli_bin_path(&prefix);
+            local_health = snailer_cli_check(&local_bin, Some(&bundled_bin)).unwrap_or(CliHealth::Broken);
+            if let CliHealth::Ok(_) = local_health {
+                return Ok(SnailerCliStatus {
+                    installed: true,
+                    cli_path: Some(local_bin.to_string_lossy().to_string()),
+                    npm_available,
+                    using_bundled_node,
+                    bundled_node_path: if bundled_ok {
+                        Some(bundled_bin.to_string_lossy().to_string())
+                    } else {
+                        None
+                    },
+                    prefix_dir,
+                    compat: SnailerCliCompat::from_health(&local_health),
+                    // Installs that predate the marker came from the registry.
+                    install_source: read_cli_install_source(&prefix).or_else(|| Some("registry".to_string())),
+                    offline_bundle_dir,
+                });
+            }
+        }
+
+        // Report the closest miss: an incompatible local install, then an incompatible global one.
+        let compat = match (&local_health, &global_health) {
+            (CliHealth::Incompatible(_), _) => SnailerCliCompat::from_health(&local_health),
+            (_, CliHealth::Incompatible(_)) => SnailerCliCompat::from_health(&global_health),
+            _ => SnailerCliCompat::from_health(&CliHealth::Broken),
+        };
+        Ok(SnailerCliStatus {
+            installed: false,
+            cli_path: None,
+            npm_available,
+            using_bundled_node,
+            bundled_node_path: if bundled_ok {
+                Some(bundled_bin.to_string_lossy().to_string())
+            } else {
+                None
+            },
+            prefix_dir,
+            compat,
+            install_source: None,
+            offline_bundle_dir,
+        })
+    })
+    .await
+    .map_err(|e| format!("Status task failed: {}", e))?
+}
+
+/// Start (or reuse) a Snailer daemon.
+///
+/// Engines are keyed by `engineId` if given, else by the canonical `project` path, else
+/// `"default"`. Each engine gets its own port, token and env file; calling this again for
+/// a running engine returns its existing connection info.
+///
+/// The engine counts as started once it answers an authenticated `initialize`; see
+/// [`daemon_startup_timeout`] for how long that may take.
+#[tauri::command]
+pub async fn engine_start(
+    app: tauri::AppHandle,
+    project: Option<String>,
+    engine_id: Option<String>,
+    env_file: Option<String>,
+    startup_timeout_secs: Option<u64>,
+) -> Result<EngineStartResponse, String> {
+    let project_dir = match non_empty_trimmed(project) {
+        Some(p) => {
+            let dir = PathBuf::from(&p);
+            if !dir.is_dir() {
+                return Err(format!("project is not a directory: {}", p));
+            }
+            Some(std::fs::canonicalize(&dir).unwrap_or(dir))
+        }
+        None => None,
+    };
+    let id = resolve_engine_id(engine_id.as_deref(), project_dir.as_deref());
+
+    {
+        let mut guard = engines()
+            .lock()
+            .map_err(|_| "engine state lock poisoned".to_string())?;
+
+        if let Some(st) = guard.get_mut(&id) {
+            // If the daemon process died, restart.
+            if st.is_running() {
+                return Ok(st.start_response());
+            }
+            guard.remove(&id);
+        }
+    }
+
+    // Shared by default (or the active env profile) so API keys are reusable across workspaces;
+    // callers may pin a per-engine file.
+    let shared_env = ensure_shared_env_selected().ok();
+
+    let token = uuid::Uuid::new_v4().to_string();
+    let startup_timeout = daemon_startup_timeout(startup_timeout_secs);
+
+    // Launch external Snailer daemon via the npm-installed CLI.
+    // No progress events here: the install UI listens for `install://progress` and would
+    // otherwise flash "ready" on every start.
+    let cli_bin = tauri::async_runtime::spawn_blocking(|| ensure_cli_installed(&InstallReporter::default()))
+        .await
+        .map_err(|e| format!("Install task failed: {}", e))??;
+    let cli_bin_for_state = cli_bin.clone();
+    let token_for_daemon = token.clone();
+    let env_file = non_empty_trimmed(env_file)
+        .map(PathBuf::from)
+        .or_else(current_env_file)
+        .or_else(|| gui_settings().snailer_env_file.map(PathBuf::from))
+        .or(shared_env)
+        .or_else(|| shared_env_path().ok());
+    let env_file = env_file.map(|p| p.to_string_lossy().to_string());
+    let env_file_for_daemon = env_file.clone();
+    let project_path = project_dir
+        .clone()
+        .unwrap_or_else(default_project_path)
+        .to_string_lossy()
+        .to_string();
+    let auth_addr_for_daemon = resolve_auth_addr().ok();
+    let openai_bearer_for_daemon = non_empty_trimmed(resolve_openai_bearer_for_gui().ok());
+
+    let log_sink = Arc::new(Mutex::new(DaemonLogSink::open(snailer_logs_dir(), &id)?));
+    let project_path_for_ready = project_path.clone();
+    let id_for_rpc = id.clone();
+
+    let (child, port, rpc) = tauri::async_runtime::spawn_blocking(move || -> Result<(std::process::Child, u16, Option<Arc<DaemonRpcClient>>), String> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let port = find_free_port().map_err(|e| format!("failed to pick free port: {}", e))?;
+
+            let mut cmd = std::process::Command::new(&cli_bin);
+            cmd.arg("daemon")
+                .arg("--port")
+                .arg(port.to_string())
+                .arg("--token")
+                .arg(&token_for_daemon);
+
+            if let Some(p) = env_file_for_daemon.as_deref() {
+                cmd.env("SNAILER_ENV_FILE", p);
+            }
+            if let Some(dir) = project_dir.as_deref() {
+                cmd.current_dir(dir);
+            }
+            if let Some(addr) = auth_addr_for_daemon.as_deref() {
+                cmd.env("SNAILER_AUTH_ADDR", addr);
+            }
+            if let Some(openai_bearer) = openai_bearer_for_daemon.as_deref() {
+                // Match CLI priority: use connected OpenAI account token first for GPT models.
+                cmd.env("OPENAI_API_KEY", openai_bearer);
+            }
+
+            // If we installed Node/npm under ~/.snailer/node/current, ensure it's on PATH
+            // so `#!/usr/bin/env node` shims work.
+            let node_bin = snailer_node_current_bin_dir();
+            if node_bin.is_dir() {
+                cmd.env("PATH", prepend_path(&node_bin));
+            }
+
+            // Avoid inherited interactive prompts from npm.
+            cmd.env("CI", "true");
+
+            // Capture output: a packaged app has no terminal to inherit stdio from.
+            cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+            record_daemon_log(
+                &log_sink,
+                &app,
+                "gui",
+                format!("starting daemon: {} daemon --port {}", cli_bin, port),
+            );
+            // Earlier attempts' output is still in the shared sink; only judge this one's.
+            let spawn_mark = log_sink.lock().map(|sink| sink.appended).unwrap_or(0);
+            let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn Snailer daemon: {e}"))?;
+            if let Some(out) = child.stdout.take() {
+                pipe_daemon_output(out, "stdout", log_sink.clone(), app.clone());
+            }
+            if let Some(err) = child.stderr.take() {
+                pipe_daemon_output(err, "stderr", log_sink.clone(), app.clone());
+            }
+
+            let err = match wait_for_daemon_ready(
+                &mut child,
+                port,
+                &token_for_daemon,
+                &project_path_for_ready,
+                startup_timeout,
+            ) {
+                Ok(()) => {
+                    // Keep a Rust-side connection so notifications flow even with no window open.
+                    let rpc = match DaemonRpcClient::connect(
+                        app.clone(),
+                        &id_for_rpc,
+                        port,
+                        &token_for_daemon,
+                        &project_path_for_ready,
+                    ) {
+                        Ok(client) => Some(client),
+                        Err(e) => {
+                            record_daemon_log(&log_sink, &app, "gui", format!("rpc client connect failed: {}", e));
+                            None
+                        }
+                    };
+                    return Ok((child, port, rpc));
+                }
+                Err(e) => e,
+            };
+            if child.try_wait().ok().flatten().is_none() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            // Output pipes may still be draining after exit; give them a moment.
+            std::thread::sleep(Duration::from_millis(100));
+            let recent = log_sink
+                .lock()
+                .map(|sink| sink.recent_output(spawn_mark, 20))
+                .unwrap_or_default();
+
+            let (message, status_text) = match err {
+                DaemonReadyError::Incompatible(e) => {
+                    record_daemon_log(&log_sink, &app, "gui", format!("capability handshake failed: {}", e));
+                    return Err(e);
+                }
+                DaemonReadyError::Exited(status) => {
+                    if attempt < DAEMON_PORT_RACE_ATTEMPTS && is_port_conflict_output(&recent) {
+                        record_daemon_log(
+                            &log_sink,
+                            &app,
+                            "gui",
+                            format!("port {} was taken before the daemon bound it; retrying", port),
+                        );
+                        continue;
+                    }
+                    (
+                        "Snailer daemon did not become ready".to_string(),
+                        format!("exited early with status {}", status),
+                    )
+                }
+                DaemonReadyError::TimedOut(e) => (e, "was still running and has been stopped".to_string()),
+            };
+            let output = if recent.is_empty() {
+                "(no output)".to_string()
+            } else {
+                recent.join("\n")
+            };
+            record_daemon_log(&log_sink, &app, "gui", format!("daemon start failed: {}", message));
+            return Err(format!(
+                "{}. Daemon {}. cli_bin={}\nRecent daemon output:\n{}",
+                message,
+                status_text,
+                cli_bin,
+                output
+            ));
+        }
+    })
+    .await
+    .map_err(|e| format!("daemon spawn task failed: {}", e))??;
+    let url = format!("ws://127.0.0.1:{port}");
+
+    let mut state = EngineState {
+        id: id.clone(),
+        project_path,
+        url,
+        port,
+        token,
+        env_file,
+        cli_bin: cli_bin_for_state,
+        started_at: chrono::Utc::now().timestamp_millis(),
+        child: Some(child),
+        rpc,
+    };
+
+    let mut guard = engines()
+        .lock()
+        .map_err(|_| "engine state lock poisoned".to_string())?;
+    if let Some(existing) = guard.get_mut(&id) {
+        // A concurrent start for the same engine won the race; keep theirs.
+        if existing.is_running() {
+            state.kill();
+            return Ok(existing.start_response());
+        }
+    }
+    let resp = state.start_response();
+    if let Some(pid) = state.child.as_ref().map(|c| c.id()) {
+        daemon_pidfile_add(DaemonPidRecord {
+            engine_id: id.clone(),
+            pid,
+            port,
+            gui_pid: std::process::id(),
+            started_at: state.started_at,
+        });
+    }
+    guard.insert(id, state);
+    Ok(resp)
+}
+
+fn non_empty_trimmed(value: Option<String>) -> Option<String> {
+    value.and_then(|raw| {
+        let trimmed = raw.trim().to_string();
+        if trimmed.is_empty() {
+            None
+        } else {
+            Some(trimmed)
+        }
+    })
+}
+
+/// Stop one engine by id, or every managed engine when `id` is omitted.
+///
+/// Daemons get a shutdown request and a short grace period before being killed.
+#[tauri::command]
+pub async fn engine_kill(id: Option<String>) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || match non_empty_trimmed(id) {
+        Some(id) => {
+            let removed = engines()
+                .lock()
+                .map_err(|_| "engine state lock poisoned".to_string())?
+                .remove(&id);
+            if let Some(st) = removed {
+                shutdown_engines(vec![st]);
+            }
+            Ok(())
+        }
+        None => {
+            engine_kill_all();
+            Ok(())
+        }
+    })
+    .await
+    .map_err(|e| format!("engine kill task failed: {}", e))?
+}
+
+/// Gracefully stop every managed engine. Used on window close and app exit.
+pub fn engine_kill_all() {
+    let states: Vec<EngineState> = {
+        let mut guard = engines().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        guard.drain().map(|(_, st)| st).collect()
+    };
+    if !states.is_empty() {
+        shutdown_engines(states);
+    }
+}
+
+/// Stop managed engines when the main thread panics; the app is going down and would
+/// otherwise leave its daemons running until the next launch reaps them. Panics on other
+/// threads don't end the app, so engines are left alone.
+pub fn install_engine_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().name() == Some("main") {
+            // `try_lock`: the panic may have happened while this thread held the lock.
+            let states: Vec<EngineState> = match engines().try_lock() {
+                Ok(mut guard) => guard.drain().map(|(_, st)| st).collect(),
+                Err(std::sync::TryLockError::Poisoned(poisoned)) => {
+                    poisoned.into_inner().drain().map(|(_, st)| st).collect()
+                }
+                Err(std::sync::TryLockError::WouldBlock) => Vec::new(),
+            };
+            if !states.is_empty() {
+                shutdown_engines(states);
             }
         }
+        previous(info);
+    }));
+}
 
-        Ok(SnailerCliStatus {
-            installed: false,
-            cli_path: None,
-            npm_available,
-            using_bundled_node,
-            bundled_node_path: if bundled_ok {
-                Some(bundled_bin.to_string_lossy().to_string())
-            } else {
-                None
-            },
-            prefix_dir,
-        })
+/// List managed engines, dropping entries whose daemon has exited.
+#[tauri::command]
+pub fn engine_list() -> Result<Vec<EngineInfo>, String> {
+    let mut guard = engines()
+        .lock()
+        .map_err(|_| "engine state lock poisoned".to_string())?;
+    guard.retain(|_, st| st.is_running());
+    let mut out: Vec<EngineInfo> = guard.values_mut().map(|st| st.info()).collect();
+    out.sort_by_key(|e| e.started_at);
+    Ok(out)
+}
+
+/// Return recent daemon output from `~/.snailer/logs/daemon-*.log`.
+///
+/// `since` is a unix-millis lower bound; `tail_lines` caps the result (default 500).
+/// `engineId` selects the engine (defaults to `"default"`).
+#[tauri::command]
+pub async fn engine_logs(
+    tail_lines: Option<usize>,
+    since: Option<i64>,
+    engine_id: Option<String>,
+) -> Result<Vec<EngineLogLine>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let id = non_empty_trimmed(engine_id).unwrap_or_else(|| DEFAULT_ENGINE_ID.to_string());
+        Ok(read_daemon_logs(&snailer_logs_dir(), &id, tail_lines.unwrap_or(500), since))
     })
     .await
-    .map_err(|e| format!("Status task failed: {}", e))?
+    .map_err(|e| format!("log read task failed: {}", e))?
 }
 
-#[tauri::command]
-pub async fn engine_start() -> Result<EngineStartResponse, String> {
-    {
-        let mut guard = engine_state()
+/// Engine client for Rust-side callers: reuse the engine's connection, reconnecting if it dropped.
+fn engine_rpc_client(app: &tauri::AppHandle, id: &str) -> Result<Arc<DaemonRpcClient>, String> {
+    let (port, token, project_path) = {
+        let mut guard = engines()
             .lock()
             .map_err(|_| "engine state lock poisoned".to_string())?;
+        let st = guard
+            .get_mut(id)
+            .ok_or_else(|| format!("engine not running: {}", id))?;
+        if !st.is_running() {
+            return Err(format!("engine not running: {}", id));
+        }
+        if let Some(client) = st.rpc.as_ref().filter(|c| !c.is_closed()) {
+            return Ok(client.clone());
+        }
+        (st.port, st.token.clone(), st.project_path.clone())
+    };
 
-        if let Some(ref mut st) = *guard {
-            if let Some(ref mut child) = st.child {
-                // If the daemon process died, restart.
-                if child.try_wait().ok().flatten().is_some() {
-                    *guard = None;
-                } else {
-                    return Ok(EngineStartResponse {
-                        url: st.url.clone(),
-                        token: st.token.clone(),
-                        default_project_path: default_project_path().to_string_lossy().to_string(),
-                    });
-                }
-            } else {
-                return Ok(EngineStartResponse {
-                    url: st.url.clone(),
-                    token: st.token.clone(),
-                    default_project_path: default_project_path().to_string_lossy().to_string(),
-                });
+    // Connect without holding the registry lock; the handshake can take a few seconds.
+    let client = DaemonRpcClient::connect(app.clone(), id, port, &token, &project_path)?;
+    let mut guard = engines()
+        .lock()
+        .map_err(|_| "engine state lock poisoned".to_string())?;
+    match guard.get_mut(id) {
+        Some(st) if st.port == port => {
+            if let Some(existing) = st.rpc.as_ref().filter(|c| !c.is_closed()) {
+                client.close();
+                return Ok(existing.clone());
             }
+            st.rpc = Some(client.clone());
+            Ok(client)
+        }
+        _ => {
+            client.close();
+            Err(format!("engine not running: {}", id))
         }
     }
+}
 
-    // Shared-only: always use `~/.snailer/.env` so API keys are reusable across workspaces.
-    let shared_env = ensure_shared_env_selected().ok();
+/// Send a JSON-RPC request to an engine's daemon over its Rust-side connection.
+///
+/// `engineId` defaults to `"default"`. Daemon notifications are emitted on `engine://notification`.
+#[tauri::command]
+pub async fn engine_request(
+    app: tauri::AppHandle,
+    method: String,
+    params: Option<serde_json::Value>,
+    engine_id: Option<String>,
+) -> Result<serde_json::Value, String> {
+    let method = non_empty_trimmed(Some(method)).ok_or_else(|| "method is required".to_string())?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let id = non_empty_trimmed(engine_id).unwrap_or_else(|| DEFAULT_ENGINE_ID.to_string());
+        let client = engine_rpc_client(&app, &id)?;
+        client.request(
+            &method,
+            params.unwrap_or(serde_json::Value::Null),
+            DAEMON_REQUEST_TIMEOUT,
+        )
+    })
+    .await
+    .map_err(|e| format!("engine request task failed: {}", e))?
+}
 
-    let port = find_free_port().map_err(|e| format!("failed to pick free port: {}", e))?;
-    let token = uuid::Uuid::new_v4().to_string();
-    let url = format!("ws://127.0.0.1:{port}");
+/// Resource usage for one process in an engine's tree.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineProcessStats {
+    pub pid: u32,
+    pub parent_pid: Option<u32>,
+    pub name: String,
+    pub rss_bytes: u64,
+    pub cpu_percent: f32,
+    pub open_fds: Option<usize>,
+    pub uptime_secs: u64,
+}
 
-    // Launch external Snailer daemon via the npm-installed CLI.
-    let cli_bin = snailer_cli_ensure_installed().await?;
-    let token_for_daemon = token.clone();
-    let env_file = read_gui_settings_env_file()
-        .map(PathBuf::from)
-        .or(shared_env)
-        .or_else(|| shared_env_path().ok());
-    let env_file = env_file.map(|p| p.to_string_lossy().to_string());
-    let auth_addr_for_daemon = resolve_auth_addr().ok();
-    let openai_bearer_for_daemon = non_empty_trimmed(resolve_openai_bearer_for_gui().ok());
+/// Resource usage for a managed daemon; totals include its child processes.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineStats {
+    pub engine_id: String,
+    pub pid: Option<u32>,
+    pub uptime_secs: u64,
+    pub rss_bytes: u64,
+    pub cpu_percent: f32,
+    pub open_fds: Option<usize>,
+    pub processes: Vec<EngineProcessStats>,
+    /// Thresholds from `engineLimits` that this sample exceeds.
+    pub warnings: Vec<String>,
+}
 
-    let child = tauri::async_runtime::spawn_blocking(move || -> Result<std::process::Child, String> {
-        let mut cmd = std::process::Command::new(&cli_bin);
-        cmd.arg("daemon")
-            .arg("--port")
-            .arg(port.to_string())
-            .arg("--token")
-            .arg(token_for_daemon);
+fn read_gui_settings_engine_limits() -> EngineLimits {
+    gui_settings().engine_limits.unwrap_or_default()
+}
 
-        if let Some(p) = env_file.as_deref() {
-            cmd.env("SNAILER_ENV_FILE", p);
+fn engine_limit_breaches(stats: &EngineStats, limits: &EngineLimits) -> Vec<String> {
+    let mut out = Vec::new();
+    if let Some(max) = limits.max_rss_mb {
+        let rss_mb = stats.rss_bytes / (1024 * 1024);
+        if rss_mb > max {
+            out.push(format!("rss {} MB exceeds limit {} MB", rss_mb, max));
         }
-        if let Some(addr) = auth_addr_for_daemon.as_deref() {
-            cmd.env("SNAILER_AUTH_ADDR", addr);
+    }
+    if let Some(max) = limits.max_cpu_percent {
+        if stats.cpu_percent > max {
+            out.push(format!("cpu {:.1}% exceeds limit {:.1}%", stats.cpu_percent, max));
         }
-        if let Some(openai_bearer) = openai_bearer_for_daemon.as_deref() {
-            // Match CLI priority: use connected OpenAI account token first for GPT models.
-            cmd.env("OPENAI_API_KEY", openai_bearer);
+    }
+    if let (Some(max), Some(fds)) = (limits.max_open_fds, stats.open_fds) {
+        if fds > max {
+            out.push(format!("{} open files exceeds limit {}", fds, max));
         }
+    }
+    out
+}
 
-        // If we installed Node/npm under ~/.snailer/node/current, ensure it's on PATH
-        // so `#!/usr/bin/env node` shims work.
-        let node_bin = snailer_node_current_bin_dir();
-        if node_bin.is_dir() {
-            cmd.env("PATH", prepend_path(&node_bin));
-        }
+/// Shared sampler: CPU% is a delta between refreshes, so the `System` must outlive one call.
+fn process_sampler() -> &'static Mutex<Option<sysinfo::System>> {
+    static SAMPLER: OnceLock<Mutex<Option<sysinfo::System>>> = OnceLock::new();
+    SAMPLER.get_or_init(|| Mutex::new(None))
+}
 
-        // Avoid inherited interactive prompts from npm.
-        cmd.env("CI", "true");
+/// Parent PID -> child PIDs, leaving out thread entries (sysinfo lists Linux threads as
+/// processes sharing the parent's memory and fds).
+fn process_children(system: &sysinfo::System) -> HashMap<u32, Vec<u32>> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (pid, proc_) in system.processes() {
+        if proc_.thread_kind().is_some() {
+            continue;
+        }
+        if let Some(parent) = proc_.parent() {
+            children.entry(parent.as_u32()).or_default().push(pid.as_u32());
+        }
+    }
+    children
+}
 
-        let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn Snailer daemon: {e}"))?;
-        if let Err(e) = wait_for_port(port, Duration::from_secs(8)) {
-            let status = child.try_wait().ok().flatten();
-            let status_text = status
-                .map(|s| format!("exited early with status {}", s))
-                .unwrap_or_else(|| "did not exit but did not bind port".to_string());
-            if status.is_none() {
-                let _ = child.kill();
-                let _ = child.wait();
-            }
-            return Err(format!(
-                "{}. Daemon {}. cli_bin={}",
-                e,
-                status_text,
-                cli_bin
-            ));
+/// `root` and its descendant processes.
+fn process_tree(system: &sysinfo::System, children: &HashMap<u32, Vec<u32>>, root: u32) -> Vec<EngineProcessStats> {
+    let mut processes = Vec::new();
+    let mut queue = vec![root];
+    while let Some(p) = queue.pop() {
+        let Some(proc_) = system.process(sysinfo::Pid::from_u32(p)) else {
+            continue;
+        };
+        processes.push(EngineProcessStats {
+            pid: p,
+            parent_pid: proc_.parent().map(|pp| pp.as_u32()),
+            name: proc_.name().to_string_lossy().to_string(),
+            rss_bytes: proc_.memory(),
+            cpu_percent: proc_.cpu_usage(),
+            open_fds: proc_.open_files(),
+            uptime_secs: proc_.run_time(),
+        });
+        if let Some(kids) = children.get(&p) {
+            queue.extend(kids.iter().copied());
         }
-        Ok(child)
-    })
-    .await
-    .map_err(|e| format!("daemon spawn task failed: {}", e))??;
+    }
+    processes
+}
 
-    {
-        let mut guard = engine_state()
+fn collect_engine_stats(limits: &EngineLimits) -> Result<Vec<EngineStats>, String> {
+    let targets: Vec<(String, Option<u32>, i64)> = {
+        let mut guard = engines()
             .lock()
             .map_err(|_| "engine state lock poisoned".to_string())?;
-        *guard = Some(EngineState {
-            url: url.clone(),
-            token: token.clone(),
-            child: Some(child),
-        });
+        guard.retain(|_, st| st.is_running());
+        guard
+            .values()
+            .map(|st| (st.id.clone(), st.child.as_ref().map(|c| c.id()), st.started_at))
+            .collect()
+    };
+
+    let mut sampler = process_sampler()
+        .lock()
+        .map_err(|_| "process sampler lock poisoned".to_string())?;
+    // On Linux, threads would otherwise be listed as child processes and counted again.
+    let refresh_kind = sysinfo::ProcessRefreshKind::nothing()
+        .with_memory()
+        .with_cpu()
+        .without_tasks();
+    let first_sample = sampler.is_none();
+    let system = sampler.get_or_insert_with(sysinfo::System::new);
+    system.refresh_processes_specifics(sysinfo::ProcessesToUpdate::All, true, refresh_kind);
+    if first_sample {
+        std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+        system.refresh_processes_specifics(sysinfo::ProcessesToUpdate::All, true, refresh_kind);
+    }
+
+    let children = process_children(system);
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let mut out = Vec::new();
+    for (engine_id, pid, started_at) in targets {
+        let processes = pid.map(|p| process_tree(system, &children, p)).unwrap_or_default();
+        let open_fds = processes
+            .iter()
+            .map(|p| p.open_fds)
+            .try_fold(0usize, |acc, n| n.map(|n| acc + n));
+        let mut stats = EngineStats {
+            engine_id,
+            pid,
+            uptime_secs: ((now_ms - started_at).max(0) / 1000) as u64,
+            rss_bytes: processes.iter().map(|p| p.rss_bytes).sum(),
+            cpu_percent: processes.iter().map(|p| p.cpu_percent).sum(),
+            open_fds,
+            processes,
+            warnings: Vec::new(),
+        };
+        stats.warnings = engine_limit_breaches(&stats, limits);
+        out.push(stats);
     }
+    out.sort_by(|a, b| a.engine_id.cmp(&b.engine_id));
+    Ok(out)
+}
 
-    Ok(EngineStartResponse {
-        url,
-        token,
-        default_project_path: default_project_path().to_string_lossy().to_string(),
-    })
+/// Report PID, uptime, RSS, CPU% and open files for each managed daemon and its children.
+#[tauri::command]
+pub async fn engine_stats() -> Result<Vec<EngineStats>, String> {
+    tauri::async_runtime::spawn_blocking(|| collect_engine_stats(&read_gui_settings_engine_limits()))
+        .await
+        .map_err(|e| format!("engine stats task failed: {}", e))?
 }
 
-fn non_empty_trimmed(value: Option<String>) -> Option<String> {
-    value.and_then(|raw| {
-        let trimmed = raw.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
+const ENGINE_MONITOR_INTERVAL: Duration = Duration::from_secs(30);
+const ENGINE_RESTART_COOLDOWN: Duration = Duration::from_secs(300);
+
+/// Payload for `engine://resource-warning`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineResourceWarning {
+    pub engine_id: String,
+    pub warnings: Vec<String>,
+    pub stats: EngineStats,
+    pub restarting: bool,
+}
+
+/// Background monitor: samples engines while `engineLimits` is configured, emits
+/// `engine://resource-warning` on a breach and restarts the engine if `restartOnBreach` is set.
+pub fn spawn_engine_monitor(app: tauri::AppHandle) {
+    std::thread::spawn(move || {
+        let mut last_restart: HashMap<String, std::time::Instant> = HashMap::new();
+        loop {
+            std::thread::sleep(ENGINE_MONITOR_INTERVAL);
+            let limits = read_gui_settings_engine_limits();
+            if limits.is_empty() {
+                continue;
+            }
+            let Ok(all) = collect_engine_stats(&limits) else {
+                continue;
+            };
+            for stats in all {
+                if stats.warnings.is_empty() {
+                    continue;
+                }
+                let cooled_down = last_restart
+                    .get(&stats.engine_id)
+                    .map(|t| t.elapsed() >= ENGINE_RESTART_COOLDOWN)
+                    .unwrap_or(true);
+                let restarting = limits.restart_on_breach && cooled_down;
+                let engine_id = stats.engine_id.clone();
+                let reason = stats.warnings.join("; ");
+                let _ = app.emit(
+                    "engine://resource-warning",
+                    EngineResourceWarning {
+                        engine_id: engine_id.clone(),
+                        warnings: stats.warnings.clone(),
+                        stats,
+                        restarting,
+                    },
+                );
+                if restarting {
+                    last_restart.insert(engine_id.clone(), std::time::Instant::now());
+                    restart_engine(&app, &engine_id, &reason);
+                }
+            }
         }
-    })
+    });
 }
 
-#[tauri::command]
-pub fn engine_kill() {
-    let Ok(mut guard) = engine_state().lock() else {
+/// Payload for `engine://restarted`; clients must reconnect with the new url/token.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineRestarted {
+    pub engine_id: String,
+    pub reason: String,
+    pub engine: Option<EngineStartResponse>,
+    pub error: Option<String>,
+}
+
+fn restart_engine(app: &tauri::AppHandle, engine_id: &str, reason: &str) {
+    let old = match engines().lock() {
+        Ok(mut guard) => guard.remove(engine_id),
+        Err(_) => return,
+    };
+    let Some(old) = old else {
         return;
     };
-    if let Some(mut st) = guard.take() {
-        if let Some(mut child) = st.child.take() {
-            let _ = child.kill();
-            let _ = child.wait();
+    let project = Some(old.project_path.clone());
+    let env_file = old.env_file.clone();
+    shutdown_engines(vec![old]);
+
+    let result = tauri::async_runtime::block_on(engine_start(
+        app.clone(),
+        project,
+        Some(engine_id.to_string()),
+        env_file,
+        None,
+    ));
+    let (engine, error) = match result {
+        Ok(resp) => (Some(resp), None),
+        Err(e) => (None, Some(e)),
+    };
+    let _ = app.emit(
+        "engine://restarted",
+        EngineRestarted {
+            engine_id: engine_id.to_string(),
+            reason: reason.to_string(),
+            engine,
+            error,
+        },
+    );
+}
+
+const SETTINGS_WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// One changed key in a `settings://changed` event. Values are masked (env) or redacted (JSON).
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsKeyChange {
+    pub key: String,
+    /// "added" | "removed" | "modified"
+    pub change: String,
+    pub value: Option<serde_json::Value>,
+}
+
+/// Payload for `settings://changed`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsChanged {
+    /// "env" | "guiSettings" | "budgetState"
+    pub file: String,
+    pub path: String,
+    pub keys: Vec<SettingsKeyChange>,
+}
+
+type SettingsSnapshot = std::collections::BTreeMap<String, serde_json::Value>;
+
+fn settings_snapshot(file: &str, path: &Path) -> SettingsSnapshot {
+    if file == "env" {
+        let text = std::fs::read_to_string(path).unwrap_or_default();
+        let mut out = SettingsSnapshot::new();
+        for e in crate::dotenv::EnvFile::parse(&text).entries() {
+            out.entry(e.key.clone())
+                .or_insert_with(|| serde_json::Value::String(e.value.clone()));
         }
+        return out;
+    }
+    crate::persist::read_json::<serde_json::Map<String, serde_json::Value>>(path)
+        .map(|m| m.into_iter().collect())
+        .unwrap_or_default()
+}
+
+fn masked_settings_value(file: &str, key: &str, value: &serde_json::Value) -> serde_json::Value {
+    if file == "env" {
+        return serde_json::Value::String(mask_env_value(value.as_str().unwrap_or_default()));
+    }
+    if is_secret_key_name(key) {
+        return serde_json::Value::String(REDACTED.to_string());
+    }
+    redact_json(value)
+}
+
+fn diff_settings(file: &str, old: &SettingsSnapshot, new: &SettingsSnapshot) -> Vec<SettingsKeyChange> {
+    let mut changes = Vec::new();
+    for (key, value) in new {
+        let change = match old.get(key) {
+            None => "added",
+            Some(prev) if prev != value => "modified",
+            Some(_) => continue,
+        };
+        changes.push(SettingsKeyChange {
+            key: key.clone(),
+            change: change.to_string(),
+            value: Some(masked_settings_value(file, key, value)),
+        });
+    }
+    for key in old.keys().filter(|k| !new.contains_key(*k)) {
+        changes.push(SettingsKeyChange {
+            key: key.clone(),
+            change: "removed".to_string(),
+            value: None,
+        });
+    }
+    changes
+}
+
+/// Watchers may report canonical paths (e.g. `/private/var` on macOS) for files we know by
+/// their configured path; compare by file name and canonical parent directory.
+fn same_watched_path(event_path: &Path, path: &Path) -> bool {
+    if event_path == path {
+        return true;
+    }
+    let canon_parent = |p: &Path| p.parent().and_then(|d| d.canonicalize().ok());
+    event_path.file_name() == path.file_name()
+        && canon_parent(event_path).is_some()
+        && canon_parent(event_path) == canon_parent(path)
+}
+
+/// Ask running daemons to re-read their env file. Best effort: older daemons don't know
+/// `env.reload`, and a failure just means they keep the old values until restart.
+fn signal_daemons_env_reload(env_path: &Path) {
+    let clients: Vec<Arc<DaemonRpcClient>> = match engines().lock() {
+        Ok(guard) => guard.values().filter_map(|st| st.rpc.clone()).collect(),
+        Err(_) => return,
+    };
+    let env_file = env_path.to_string_lossy().to_string();
+    for client in clients {
+        let params = serde_json::json!({ "envFile": env_file });
+        std::thread::spawn(move || {
+            let _ = client.request("env.reload", params, Duration::from_secs(5));
+        });
     }
 }
 
+/// Watch the active env file, `gui_settings.json` and `budget_state.json` and emit debounced
+/// `settings://changed` events. Directories are watched rather than files, since atomic writes
+/// replace the file. Set `reloadDaemonOnEnvChange: true` in gui_settings to also send
+/// `env.reload` to running daemons.
+pub fn spawn_settings_watcher(app: tauri::AppHandle) {
+    use notify_debouncer_mini::notify::RecursiveMode;
+
+    std::thread::spawn(move || {
+        let home = snailer_home_dir();
+        let _ = std::fs::create_dir_all(&home);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let Ok(mut debouncer) = notify_debouncer_mini::new_debouncer(SETTINGS_WATCH_DEBOUNCE, tx) else {
+            return;
+        };
+        if debouncer.watcher().watch(&home, RecursiveMode::NonRecursive).is_err() {
+            return;
+        }
+
+        let gui_path = gui_settings_path();
+        let budget_path = home.join("budget_state.json");
+        let mut env_path = active_env_path().unwrap_or_else(|_| home.join(".env"));
+        let mut env_dir: Option<PathBuf> = None;
+        let mut snapshots: HashMap<&'static str, SettingsSnapshot> = HashMap::new();
+        snapshots.insert("guiSettings", settings_snapshot("guiSettings", &gui_path));
+        snapshots.insert("budgetState", settings_snapshot("budgetState", &budget_path));
+        snapshots.insert("env", settings_snapshot("env", &env_path));
+
+        loop {
+            // Profiles and custom env files live outside `~/.snailer`; follow the active one.
+            let wanted_dir = env_path.parent().map(|p| p.to_path_buf()).filter(|d| *d != home);
+            if wanted_dir != env_dir {
+                if let Some(old) = env_dir.take() {
+                    let _ = debouncer.watcher().unwatch(&old);
+                }
+                if let Some(dir) = wanted_dir.as_ref() {
+                    if debouncer.watcher().watch(dir, RecursiveMode::NonRecursive).is_ok() {
+                        env_dir = wanted_dir.clone();
+                    }
+                }
+            }
+
+            let Ok(result) = rx.recv() else {
+                return;
+            };
+            let Ok(events) = result else {
+                continue;
+            };
+            let touched = |p: &Path| events.iter().any(|e| same_watched_path(&e.path, p));
+
+            let mut changed: Vec<(&'static str, PathBuf)> = Vec::new();
+            if touched(&gui_path) {
+                changed.push(("guiSettings", gui_path.clone()));
+            }
+            if touched(&budget_path) {
+                changed.push(("budgetState", budget_path.clone()));
+            }
+            // A profile switch in gui_settings retargets the env file even if no env file was touched.
+            let next_env = active_env_path().unwrap_or_else(|_| home.join(".env"));
+            if next_env != env_path || touched(&env_path) {
+                if next_env != env_path && current_env_file().is_some() {
+                    set_current_env_file(Some(next_env.clone()));
+                }
+                env_path = next_env;
+                changed.push(("env", env_path.clone()));
+            }
+
+            for (file, path) in changed {
+                let next = settings_snapshot(file, &path);
+                let prev = snapshots.insert(file, next.clone()).unwrap_or_default();
+                let keys = diff_settings(file, &prev, &next);
+                if keys.is_empty() {
+                    continue;
+                }
+                if file == "env"
+                    && gui_settings().reload_daemon_on_env_change
+                {
+                    signal_daemons_env_reload(&path);
+                }
+                let _ = app.emit(
+                    "settings://changed",
+                    SettingsChanged {
+                        file: file.to_string(),
+                        path: path.to_string_lossy().to_string(),
+                        keys,
+                    },
+                );
+            }
+        }
+    });
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FileNode {
@@ -2034,9 +5708,8 @@ pub async fn env_find(project_path: String) -> Result<EnvFindResponse, String> {
     let mut all_found_paths: Vec<String> = Vec::new();
 
     // 1) SNAILER_ENV_FILE
-    if let Ok(custom) = std::env::var("SNAILER_ENV_FILE") {
-        demonstrate_order.push(format!("SNAILER_ENV_FILE={}", custom));
-        let p = PathBuf::from(custom);
+    if let Some(p) = current_env_file() {
+        demonstrate_order.push(format!("SNAILER_ENV_FILE={}", p.display()));
         if p.is_file() {
             let s = p.to_string_lossy().to_string();
             all_found_paths.push(s.clone());
@@ -2112,19 +5785,145 @@ pub async fn env_find(project_path: String) -> Result<EnvFindResponse, String> {
                     all_found_paths,
                 });
             }
-        } else {
-            demonstrate_order.push("exe parent=<unavailable>".to_string());
+        } else {
+            demonstrate_order.push("exe parent=<unavailable>".to_string());
+        }
+    } else {
+        demonstrate_order.push("exe=<unavailable>".to_string());
+    }
+
+    Ok(EnvFindResponse {
+        found: false,
+        demonstrate_order,
+        selected_path: None,
+        all_found_paths,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvCandidateFile {
+    pub path: String,
+    /// "SNAILER_ENV_FILE" | "activeProfile" | "projectPath" | "projectAncestor" | "cwdAncestor" | "exeAncestor"
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvKeyResolution {
+    pub key: String,
+    /// File whose value is used (first in candidate order), and the line it's on.
+    pub winner: String,
+    pub line: usize,
+    pub masked_value: String,
+    /// Later files that also define the key; their values are ignored.
+    pub shadowed_in: Vec<String>,
+    /// The process environment already has the key; dotenv loading won't override it.
+    pub process_override: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvResolveResponse {
+    pub files: Vec<EnvCandidateFile>,
+    pub keys: Vec<EnvKeyResolution>,
+}
+
+fn env_files_in_ancestors(dir: &Path) -> Vec<PathBuf> {
+    dir.ancestors()
+        .map(|d| d.join(".env"))
+        .filter(|p| p.is_file())
+        .collect()
+}
+
+/// Every existing `.env` candidate, in the same order `env_find` checks them.
+fn env_candidate_files(project_path: &str) -> Vec<(String, PathBuf)> {
+    let mut out: Vec<(String, PathBuf)> = Vec::new();
+    match current_env_file() {
+        Some(custom) => out.push(("SNAILER_ENV_FILE".to_string(), custom)),
+        // Not selected yet (no engine started); engine_start will point it at the active profile.
+        None => {
+            if let Ok(p) = active_env_path() {
+                out.push(("activeProfile".to_string(), p));
+            }
+        }
+    }
+    if !project_path.trim().is_empty() {
+        let root = PathBuf::from(project_path);
+        out.push(("projectPath".to_string(), root.join(".env")));
+        for p in env_files_in_ancestors(&root) {
+            out.push(("projectAncestor".to_string(), p));
+        }
+    }
+    if let Ok(cwd) = std::env::current_dir() {
+        for p in env_files_in_ancestors(&cwd) {
+            out.push(("cwdAncestor".to_string(), p));
+        }
+    }
+    if let Some(dir) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(|d| d.to_path_buf())) {
+        for p in env_files_in_ancestors(&dir) {
+            out.push(("exeAncestor".to_string(), p));
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    out.retain(|(_, p)| p.is_file() && seen.insert(p.canonicalize().unwrap_or_else(|_| p.clone())));
+    out
+}
+
+fn resolve_env_keys(files: &[(String, PathBuf)], in_process_env: impl Fn(&str) -> bool) -> Vec<EnvKeyResolution> {
+    let mut keys: std::collections::BTreeMap<String, EnvKeyResolution> = std::collections::BTreeMap::new();
+    for (_, path) in files {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        let path_str = path.to_string_lossy().to_string();
+        for entry in crate::dotenv::EnvFile::parse(&text).entries() {
+            match keys.get_mut(&entry.key) {
+                Some(existing) => {
+                    if existing.winner != path_str && !existing.shadowed_in.contains(&path_str) {
+                        existing.shadowed_in.push(path_str.clone());
+                    }
+                }
+                None => {
+                    keys.insert(
+                        entry.key.clone(),
+                        EnvKeyResolution {
+                            key: entry.key.clone(),
+                            winner: path_str.clone(),
+                            line: entry.line,
+                            masked_value: mask_env_value(&entry.value),
+                            shadowed_in: Vec::new(),
+                            process_override: in_process_env(&entry.key),
+                        },
+                    );
+                }
+            }
         }
-    } else {
-        demonstrate_order.push("exe=<unavailable>".to_string());
     }
+    keys.into_values().collect()
+}
 
-    Ok(EnvFindResponse {
-        found: false,
-        demonstrate_order,
-        selected_path: None,
-        all_found_paths,
+/// Trace every `.env` candidate (same order as `env_find`) and report, per key, which file
+/// wins, which files it is shadowed in, and whether the process environment overrides it.
+#[tauri::command]
+pub async fn env_resolve(project_path: String) -> Result<EnvResolveResponse, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let files = env_candidate_files(&project_path);
+        let keys = resolve_env_keys(&files, |k| std::env::var_os(k).is_some());
+        EnvResolveResponse {
+            files: files
+                .into_iter()
+                .map(|(source, p)| EnvCandidateFile {
+                    path: p.to_string_lossy().to_string(),
+                    source,
+                })
+                .collect(),
+            keys,
+        }
     })
+    .await
+    .map_err(|e| format!("join error: {}", e))
 }
 
 /// Ensure `<projectPath>/.env` exists (creates an empty file if missing).
@@ -2169,6 +5968,30 @@ struct BudgetState {
     spent_main: f32,
     monthly_limit_minimax: f32,
     monthly_limit_main: f32,
+    /// Day of month the `spent_today` counters belong to.
+    #[serde(default)]
+    day: u32,
+    #[serde(default)]
+    agent_limits: std::collections::BTreeMap<String, BudgetCap>,
+    #[serde(default)]
+    provider_limits: std::collections::BTreeMap<String, BudgetCap>,
+    #[serde(default)]
+    agent_spend: std::collections::BTreeMap<String, BudgetSpend>,
+    #[serde(default)]
+    provider_spend: std::collections::BTreeMap<String, BudgetSpend>,
+}
+
+/// Daily and monthly caps in USD for one agent or provider; `None` means uncapped.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct BudgetCap {
+    daily_limit: Option<f32>,
+    monthly_limit: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct BudgetSpend {
+    spent_today: f32,
+    spent_month: f32,
 }
 
 fn budget_state_path() -> Result<PathBuf, String> {
@@ -2187,42 +6010,76 @@ fn default_main_limit_for_plan(plan: &str) -> f32 {
     }
 }
 
+/// `SNAILER_PLAN` plus the `SNAILER_BUDGET_MAIN` / `SNAILER_BUDGET_MINIMAX` overrides.
+fn budget_env() -> (String, Option<f32>, Option<f32>) {
+    let plan = std::env::var("SNAILER_PLAN").unwrap_or_else(|_| "starter".to_string());
+    let env_main_override = std::env::var("SNAILER_BUDGET_MAIN")
+        .ok()
+        .and_then(|v| v.parse::<f32>().ok());
+    let env_minimax_override = std::env::var("SNAILER_BUDGET_MINIMAX")
+        .ok()
+        .and_then(|v| v.parse::<f32>().ok());
+    (plan, env_main_override, env_minimax_override)
+}
+
+fn new_budget_state(now: chrono::DateTime<chrono::Utc>, limit_main: f32, limit_minimax: f32) -> BudgetState {
+    use chrono::Datelike;
+
+    BudgetState {
+        month: now.month(),
+        year: now.year(),
+        spent_minimax: 0.0,
+        spent_main: 0.0,
+        monthly_limit_minimax: limit_minimax,
+        monthly_limit_main: limit_main,
+        day: now.day(),
+        agent_limits: Default::default(),
+        provider_limits: Default::default(),
+        agent_spend: Default::default(),
+        provider_spend: Default::default(),
+    }
+}
+
+/// Reset spend counters that belong to an earlier day or month. Caps are kept.
+fn roll_budget_state(state: &mut BudgetState, now: chrono::DateTime<chrono::Utc>) {
+    use chrono::Datelike;
+
+    if state.month != now.month() || state.year != now.year() {
+        let fresh = new_budget_state(now, state.monthly_limit_main, state.monthly_limit_minimax);
+        *state = BudgetState {
+            agent_limits: std::mem::take(&mut state.agent_limits),
+            provider_limits: std::mem::take(&mut state.provider_limits),
+            ..fresh
+        };
+    } else if state.day != now.day() {
+        state.day = now.day();
+        for spend in state.agent_spend.values_mut().chain(state.provider_spend.values_mut()) {
+            spend.spent_today = 0.0;
+        }
+    }
+}
+
 fn read_budget_state(
     plan: &str,
     env_main_override: Option<f32>,
     env_minimax_override: Option<f32>,
 ) -> BudgetState {
-    use chrono::Datelike;
-
     let now = chrono::Utc::now();
     let limit_minimax = env_minimax_override.unwrap_or(13.0_f32).min(13.0_f32);
     let default_main = default_main_limit_for_plan(plan);
     let limit_main = env_main_override.unwrap_or(default_main).max(0.0);
 
-    let fresh = || BudgetState {
-        month: now.month(),
-        year: now.year(),
-        spent_minimax: 0.0,
-        spent_main: 0.0,
-        monthly_limit_minimax: limit_minimax,
-        monthly_limit_main: limit_main,
-    };
-
     let path = match budget_state_path() {
         Ok(p) => p,
-        Err(_) => return fresh(),
-    };
-    let Ok(contents) = std::fs::read_to_string(&path) else {
-        return fresh();
+        Err(_) => return new_budget_state(now, limit_main, limit_minimax),
     };
-    let Ok(mut state) = serde_json::from_str::<BudgetState>(&contents) else {
-        return fresh();
+    // Falls back to the `.bak` copy if the file was left truncated, instead of resetting spend.
+    let Some(mut state) = crate::persist::read_json::<BudgetState>(&path) else {
+        return new_budget_state(now, limit_main, limit_minimax);
     };
 
-    // Month rollover: reset spend for the new month/year.
-    if state.month != now.month() || state.year != now.year() {
-        state = fresh();
-    }
+    // Day/month rollover: reset spend for the new period.
+    roll_budget_state(&mut state, now);
 
     // Env overrides take precedence over persisted limits.
     state.monthly_limit_minimax = limit_minimax;
@@ -2230,22 +6087,68 @@ fn read_budget_state(
     state
 }
 
-fn write_budget_state(state: &BudgetState) -> Result<(), String> {
+/// Locked read-modify-write of `budget_state.json`, with day/month rollover applied first.
+fn update_budget_state(f: impl FnOnce(&mut BudgetState) -> Result<(), String>) -> Result<(), String> {
     let path = budget_state_path()?;
-    let text = serde_json::to_string(state).map_err(|e| format!("serialize failed: {}", e))?;
-    std::fs::write(&path, text).map_err(|e| format!("write failed: {}", e))?;
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        if let Ok(metadata) = std::fs::metadata(&path) {
-            let mut perms = metadata.permissions();
-            perms.set_mode(0o600);
-            let _ = std::fs::set_permissions(&path, perms);
-        }
-    }
+    crate::persist::update(&path, crate::persist::Policy::JSON, |current| {
+        let now = chrono::Utc::now();
+        let mut state = match current {
+            Some(text) => serde_json::from_str::<BudgetState>(&text)
+                .map_err(|e| format!("budget_state.json is malformed: {}", e))?,
+            None => {
+                let (plan, env_main_override, env_minimax_override) = budget_env();
+                read_budget_state(&plan, env_main_override, env_minimax_override)
+            }
+        };
+        roll_budget_state(&mut state, now);
+        f(&mut state)?;
+        serde_json::to_string(&state).map_err(|e| format!("serialize failed: {}", e))
+    })?;
+    chmod_600(&path);
     Ok(())
 }
 
+/// Spend against the caps of one agent or provider.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetBreakdown {
+    pub id: String,
+    pub daily_limit_usd: Option<f32>,
+    pub monthly_limit_usd: Option<f32>,
+    pub spent_today_usd: f32,
+    pub spent_month_usd: f32,
+    /// `"daily"` or `"monthly"` once a cap has been reached.
+    pub exceeded: Option<String>,
+}
+
+fn budget_breakdown(
+    limits: &std::collections::BTreeMap<String, BudgetCap>,
+    spend: &std::collections::BTreeMap<String, BudgetSpend>,
+) -> Vec<BudgetBreakdown> {
+    let ids: std::collections::BTreeSet<&String> = limits.keys().chain(spend.keys()).collect();
+    ids.into_iter()
+        .map(|id| {
+            let cap = limits.get(id).cloned().unwrap_or_default();
+            let spent = spend.get(id).cloned().unwrap_or_default();
+            let exceeded = if cap.monthly_limit.is_some_and(|l| spent.spent_month >= l) {
+                Some("monthly".to_string())
+            } else if cap.daily_limit.is_some_and(|l| spent.spent_today >= l) {
+                Some("daily".to_string())
+            } else {
+                None
+            };
+            BudgetBreakdown {
+                id: id.clone(),
+                daily_limit_usd: cap.daily_limit,
+                monthly_limit_usd: cap.monthly_limit,
+                spent_today_usd: spent.spent_today,
+                spent_month_usd: spent.spent_month,
+                exceeded,
+            }
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BudgetStatusResponse {
@@ -2258,20 +6161,16 @@ pub struct BudgetStatusResponse {
     pub minimax_spent_usd: f32,
     pub month: u32,
     pub year: i32,
+    pub agents: Vec<BudgetBreakdown>,
+    pub providers: Vec<BudgetBreakdown>,
 }
 
 #[tauri::command]
 pub async fn budget_get_status() -> Result<BudgetStatusResponse, String> {
     use chrono::Datelike;
 
-    let plan = std::env::var("SNAILER_PLAN").unwrap_or_else(|_| "starter".to_string());
+    let (plan, env_main_override, env_minimax_override) = budget_env();
     let is_starter = plan.to_lowercase() == "starter";
-    let env_main_override = std::env::var("SNAILER_BUDGET_MAIN")
-        .ok()
-        .and_then(|v| v.parse::<f32>().ok());
-    let env_minimax_override = std::env::var("SNAILER_BUDGET_MINIMAX")
-        .ok()
-        .and_then(|v| v.parse::<f32>().ok());
 
     let state = read_budget_state(&plan, env_main_override, env_minimax_override);
     let now = chrono::Utc::now();
@@ -2286,6 +6185,8 @@ pub async fn budget_get_status() -> Result<BudgetStatusResponse, String> {
         minimax_spent_usd: state.spent_minimax,
         month: now.month(),
         year: now.year(),
+        agents: budget_breakdown(&state.agent_limits, &state.agent_spend),
+        providers: budget_breakdown(&state.provider_limits, &state.provider_spend),
     })
 }
 
@@ -2295,92 +6196,380 @@ pub async fn budget_set_main_limit(main_limit_usd: f32) -> Result<BudgetStatusRe
         return Err("mainLimitUsd must be a non-negative number".to_string());
     }
 
-    let plan = std::env::var("SNAILER_PLAN").unwrap_or_else(|_| "starter".to_string());
-    let env_main_override = std::env::var("SNAILER_BUDGET_MAIN")
-        .ok()
-        .and_then(|v| v.parse::<f32>().ok());
-    let env_minimax_override = std::env::var("SNAILER_BUDGET_MINIMAX")
-        .ok()
-        .and_then(|v| v.parse::<f32>().ok());
-
-    let mut state = read_budget_state(&plan, env_main_override, env_minimax_override);
-    state.monthly_limit_main = main_limit_usd.max(0.0);
-    write_budget_state(&state)?;
+    update_budget_state(|state| {
+        state.monthly_limit_main = main_limit_usd.max(0.0);
+        Ok(())
+    })?;
 
     // Return refreshed snapshot
     budget_get_status().await
 }
 
-fn format_env_value(value: &str) -> String {
-    // Keep simple values unquoted; quote when whitespace or comment char could break parsing.
-    let needs_quotes = value.chars().any(|c| c.is_whitespace() || c == '#');
-    if !needs_quotes {
-        return value.to_string();
+fn budget_cap(daily_limit_usd: Option<f32>, monthly_limit_usd: Option<f32>) -> Result<BudgetCap, String> {
+    for (name, limit) in [("dailyLimitUsd", daily_limit_usd), ("monthlyLimitUsd", monthly_limit_usd)] {
+        if limit.is_some_and(|l| !l.is_finite() || l < 0.0) {
+            return Err(format!("{} must be a non-negative number", name));
+        }
     }
-    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
-    format!("\"{}\"", escaped)
+    Ok(BudgetCap {
+        daily_limit: daily_limit_usd,
+        monthly_limit: monthly_limit_usd,
+    })
 }
 
-/// Upsert a single key into `<projectPath>/.env` (creates the file if missing).
-#[tauri::command]
-pub async fn env_upsert_key(project_path: String, env_var: String, value: String) -> Result<String, String> {
-    let root = PathBuf::from(project_path);
-    if !root.is_dir() {
-        return Err("projectPath is not a directory".to_string());
-    }
-    if env_var.trim().is_empty() {
-        return Err("envVar is empty".to_string());
+fn set_budget_cap(
+    limits: &mut std::collections::BTreeMap<String, BudgetCap>,
+    id: String,
+    cap: BudgetCap,
+) {
+    if cap == BudgetCap::default() {
+        limits.remove(&id);
+    } else {
+        limits.insert(id, cap);
     }
+}
 
-    let env_path = root.join(".env");
-    let existing = std::fs::read_to_string(&env_path).unwrap_or_default();
-    let mut lines: Vec<String> = existing.split('\n').map(|s| s.trim_end_matches('\r').to_string()).collect();
-
-    let mut updated = false;
-    for line in lines.iter_mut() {
-        let raw = line.clone();
-        let trimmed_start = raw.trim_start();
-        if trimmed_start.starts_with('#') || trimmed_start.is_empty() {
-            continue;
-        }
+fn budget_id(value: &str, what: &str) -> Result<String, String> {
+    non_empty_trimmed(Some(value.to_string())).ok_or_else(|| format!("{} is empty", what))
+}
 
-        let (export_prefix, rest) = if let Some(after) = trimmed_start.strip_prefix("export ") {
-            ("export ", after.trim_start())
-        } else {
-            ("", trimmed_start)
-        };
+/// Set (or, with both limits omitted, clear) the daily/monthly caps for one agent.
+#[tauri::command]
+pub async fn budget_set_agent_limit(
+    agent_id: String,
+    daily_limit_usd: Option<f32>,
+    monthly_limit_usd: Option<f32>,
+) -> Result<BudgetStatusResponse, String> {
+    let id = budget_id(&agent_id, "agentId")?;
+    let cap = budget_cap(daily_limit_usd, monthly_limit_usd)?;
+    update_budget_state(|state| {
+        set_budget_cap(&mut state.agent_limits, id, cap);
+        Ok(())
+    })?;
+    budget_get_status().await
+}
 
-        if let Some(after_key) = rest.strip_prefix(env_var.as_str()) {
-            let after_key = after_key.trim_start();
-            if after_key.starts_with('=') {
-                let indent_len = raw.len().saturating_sub(trimmed_start.len());
-                let indent = &raw[..indent_len];
-                *line = format!(
-                    "{}{}{}={}",
-                    indent,
-                    export_prefix,
-                    env_var.trim(),
-                    format_env_value(value.trim())
-                );
-                updated = true;
-                break;
+/// Set (or, with both limits omitted, clear) the daily/monthly caps for one provider.
+#[tauri::command]
+pub async fn budget_set_provider_limit(
+    provider: String,
+    daily_limit_usd: Option<f32>,
+    monthly_limit_usd: Option<f32>,
+) -> Result<BudgetStatusResponse, String> {
+    let id = budget_id(&provider, "provider")?.to_lowercase();
+    let cap = budget_cap(daily_limit_usd, monthly_limit_usd)?;
+    update_budget_state(|state| {
+        set_budget_cap(&mut state.provider_limits, id, cap);
+        Ok(())
+    })?;
+    budget_get_status().await
+}
+
+/// One billed call, as reported by the caller of `budget_record_spend`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendRecord {
+    pub agent_id: Option<String>,
+    pub provider: String,
+    pub model: Option<String>,
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cached_input_tokens: u64,
+    #[serde(default)]
+    pub web_search_calls: u64,
+    /// Computed from the pricing table when omitted.
+    pub cost_usd: Option<f64>,
+    pub request_id: Option<String>,
+}
+
+fn spend_ledger_path() -> PathBuf {
+    snailer_home_dir().join("spend_ledger.jsonl")
+}
+
+/// Append one call to the spend ledger and add its cost to the agent's and provider's
+/// counters. The returned status carries `exceeded` flags so callers can stop before the
+/// next call.
+///
+/// A `requestId` already in the ledger is not recorded or counted again, so retries are safe.
+/// If the counter update fails after the ledger append, that retry is skipped too and the
+/// counters trail the ledger until the next monthly reset.
+#[tauri::command]
+pub async fn budget_record_spend(record: SpendRecord) -> Result<BudgetStatusResponse, String> {
+    record_spend(record)?;
+    budget_get_status().await
+}
+
+fn record_spend(record: SpendRecord) -> Result<(), String> {
+    if record.cost_usd.is_some_and(|c| !c.is_finite() || c < 0.0) {
+        return Err("costUsd must be a non-negative number".to_string());
+    }
+    let provider = budget_id(&record.provider, "provider")?.to_lowercase();
+    let agent_id = non_empty_trimmed(record.agent_id);
+    let model = non_empty_trimmed(record.model);
+    let usage = crate::pricing::Usage {
+        input_tokens: record.input_tokens,
+        output_tokens: record.output_tokens,
+        cached_input_tokens: record.cached_input_tokens,
+        web_search_calls: record.web_search_calls,
+    };
+    let (cost_usd, unpriced) = match record.cost_usd {
+        Some(cost) => (cost, false),
+        None => {
+            let table = pricing_table();
+            match model.as_deref().and_then(|m| crate::pricing::lookup(&table, m)) {
+                Some((_, price)) => (crate::pricing::cost(price, usage), false),
+                None => {
+                    log::warn!("no price for model {:?}; recording spend as unpriced", model);
+                    (0.0, true)
+                }
             }
         }
+    };
+    let entry = crate::spend_ledger::LedgerEntry {
+        ts: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        agent_id: agent_id.clone(),
+        provider: provider.clone(),
+        model,
+        input_tokens: usage.input_tokens,
+        output_tokens: usage.output_tokens,
+        cached_input_tokens: usage.cached_input_tokens,
+        web_search_calls: usage.web_search_calls,
+        cost_usd,
+        unpriced,
+        request_id: non_empty_trimmed(record.request_id),
+    };
+    // Ledger first: it's the audit record, the counters can be rebuilt from it.
+    if !crate::spend_ledger::append(&spend_ledger_path(), &entry)? {
+        return Ok(());
     }
 
-    if !updated {
-        if !lines.is_empty() && lines.last().is_some_and(|l| !l.is_empty()) {
-            lines.push(String::new());
+    let cost_usd = cost_usd as f32;
+    update_budget_state(|state| {
+        let mut buckets = vec![state.provider_spend.entry(provider).or_default()];
+        if let Some(agent_id) = agent_id {
+            buckets.push(state.agent_spend.entry(agent_id).or_default());
+        }
+        for spend in buckets {
+            spend.spent_today += cost_usd;
+            spend.spent_month += cost_usd;
         }
-        lines.push(format!("{}={}", env_var.trim(), format_env_value(value.trim())));
+        Ok(())
+    })
+}
+
+/// Record a completion made by one of the direct-API commands below. Failures are logged
+/// rather than returned: the call itself already succeeded and its result shouldn't be lost.
+fn record_completion_spend(provider: &str, resp: &LlmCompletionResponse) {
+    let record = SpendRecord {
+        agent_id: None,
+        provider: provider.to_string(),
+        model: Some(resp.model.clone()),
+        input_tokens: resp.input_tokens,
+        output_tokens: resp.output_tokens,
+        cached_input_tokens: resp.cached_input_tokens,
+        web_search_calls: resp.web_search_calls,
+        cost_usd: None,
+        request_id: None,
+    };
+    if let Err(e) = record_spend(record) {
+        log::warn!("failed to record {} spend for {}: {}", provider, resp.model, e);
     }
+}
 
-    let mut out = lines.join("\n");
-    if !out.ends_with('\n') {
-        out.push('\n');
+fn parse_ledger_date(value: Option<String>, name: &str) -> Result<Option<chrono::NaiveDate>, String> {
+    non_empty_trimmed(value)
+        .map(|v| {
+            chrono::NaiveDate::parse_from_str(&v, "%Y-%m-%d")
+                .map_err(|_| format!("{} must be a date like 2026-01-31", name))
+        })
+        .transpose()
+}
+
+fn spend_ledger_filter(
+    from: Option<String>,
+    to: Option<String>,
+    agent_id: Option<String>,
+    provider: Option<String>,
+) -> Result<crate::spend_ledger::Filter, String> {
+    Ok(crate::spend_ledger::Filter {
+        from: parse_ledger_date(from, "from")?,
+        to: parse_ledger_date(to, "to")?,
+        agent_id: non_empty_trimmed(agent_id),
+        provider: non_empty_trimmed(provider).map(|p| p.to_lowercase()),
+        model: None,
+    })
+}
+
+/// Spend totals from the ledger grouped by `day`, `month`, `agent`, `provider` or `model`,
+/// optionally limited to an inclusive UTC date range (`YYYY-MM-DD`), agent or provider.
+#[tauri::command]
+pub async fn spend_ledger_query(
+    group_by: String,
+    from: Option<String>,
+    to: Option<String>,
+    agent_id: Option<String>,
+    provider: Option<String>,
+) -> Result<Vec<crate::spend_ledger::Aggregate>, String> {
+    let group_by = crate::spend_ledger::GroupBy::parse(&group_by)?;
+    let filter = spend_ledger_filter(from, to, agent_id, provider)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let entries = crate::spend_ledger::read(&spend_ledger_path())?;
+        Ok(crate::spend_ledger::aggregate(
+            entries.iter().filter(|e| filter.matches(e)),
+            group_by,
+        ))
+    })
+    .await
+    .map_err(|e| format!("ledger query task failed: {}", e))?
+}
+
+/// Raw ledger entries, newest first, for auditing individual calls.
+#[tauri::command]
+pub async fn spend_ledger_entries(
+    from: Option<String>,
+    to: Option<String>,
+    agent_id: Option<String>,
+    provider: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<crate::spend_ledger::LedgerEntry>, String> {
+    let filter = spend_ledger_filter(from, to, agent_id, provider)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let entries = crate::spend_ledger::read(&spend_ledger_path())?;
+        Ok(entries
+            .into_iter()
+            .rev()
+            .filter(|e| filter.matches(e))
+            .take(limit.unwrap_or(500))
+            .collect())
+    })
+    .await
+    .map_err(|e| format!("ledger query task failed: {}", e))?
+}
+
+fn pricing_path() -> PathBuf {
+    snailer_home_dir().join("pricing.json")
+}
+
+/// Built-in prices plus `~/.snailer/pricing.json`. Background callers fall back to the
+/// built-in table if the user file is malformed; `pricing_get` surfaces the error instead.
+fn pricing_table() -> std::collections::BTreeMap<String, crate::pricing::ModelPrice> {
+    let overrides = crate::pricing::load_overrides(&pricing_path()).unwrap_or_else(|e| {
+        log::warn!("{}", e);
+        Default::default()
+    });
+    crate::pricing::merged(&overrides)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPricingInfo {
+    pub model: String,
+    #[serde(flatten)]
+    pub price: crate::pricing::ModelPrice,
+    /// `"default"`, `"user"` (added in pricing.json) or `"override"` (replaces a default).
+    pub source: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PricingTableResponse {
+    pub path: String,
+    pub models: Vec<ModelPricingInfo>,
+    /// Models in the spend ledger with no price; their calls were recorded at zero cost.
+    pub unpriced_models: Vec<String>,
+}
+
+fn pricing_table_response() -> Result<PricingTableResponse, String> {
+    let path = pricing_path();
+    let overrides = crate::pricing::load_overrides(&path)?;
+    let defaults = crate::pricing::defaults();
+    let table = crate::pricing::merged(&overrides);
+    let models = table
+        .iter()
+        .map(|(model, price)| ModelPricingInfo {
+            model: model.clone(),
+            price: price.clone(),
+            source: match (overrides.contains_key(model), defaults.contains_key(model)) {
+                (false, _) => "default",
+                (true, false) => "user",
+                (true, true) => "override",
+            }
+            .to_string(),
+        })
+        .collect();
+    let unpriced_models: std::collections::BTreeSet<String> = crate::spend_ledger::read(&spend_ledger_path())?
+        .into_iter()
+        .filter_map(|e| e.model)
+        .filter(|m| crate::pricing::lookup(&table, m).is_none())
+        .collect();
+    Ok(PricingTableResponse {
+        path: display_snailer_path(&path),
+        models,
+        unpriced_models: unpriced_models.into_iter().collect(),
+    })
+}
+
+/// Model prices used for budget math, with where each came from and any unpriced models seen.
+#[tauri::command]
+pub async fn pricing_get() -> Result<PricingTableResponse, String> {
+    tauri::async_runtime::spawn_blocking(pricing_table_response)
+        .await
+        .map_err(|e| format!("pricing task failed: {}", e))?
+}
+
+/// Set a model's price in `~/.snailer/pricing.json`, or with `price: null` drop the override
+/// (reverting to the built-in price, if any).
+#[tauri::command]
+pub async fn pricing_set_model(
+    model: String,
+    price: Option<crate::pricing::ModelPrice>,
+) -> Result<PricingTableResponse, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        crate::pricing::set_override(&pricing_path(), &model, price.as_ref())?;
+        pricing_table_response()
+    })
+    .await
+    .map_err(|e| format!("pricing task failed: {}", e))?
+}
+
+/// Set `env_var` in the `.env` file at `env_path`, leaving every other line as it was.
+fn upsert_env_file(env_path: &Path, env_var: &str, value: &str) -> Result<(), String> {
+    let key = env_var.trim();
+    if key.is_empty() {
+        return Err("envVar is empty".to_string());
+    }
+    if !crate::dotenv::is_valid_key(key) {
+        return Err(format!("invalid env var name: {}", key));
+    }
+    crate::persist::update(env_path, env_write_policy(env_path), |existing| {
+        let mut env = crate::dotenv::EnvFile::parse(&existing.unwrap_or_default());
+        env.set(key, value.trim());
+        Ok(env.to_string())
+    })
+}
+
+/// Env files under `~/.snailer` keep a `.bak`; project `.env` files don't, so a copy of the
+/// secrets never lands next to a repo.
+fn env_write_policy(env_path: &Path) -> crate::persist::Policy {
+    if env_path.starts_with(snailer_home_dir()) {
+        crate::persist::Policy::TEXT
+    } else {
+        crate::persist::Policy::TEXT_NO_BACKUP
     }
-    std::fs::write(&env_path, out).map_err(|e| format!("write failed: {}", e))?;
+}
 
+/// Upsert a single key into `<projectPath>/.env` (creates the file if missing).
+#[tauri::command]
+pub async fn env_upsert_key(project_path: String, env_var: String, value: String) -> Result<String, String> {
+    let root = PathBuf::from(project_path);
+    if !root.is_dir() {
+        return Err("projectPath is not a directory".to_string());
+    }
+    let env_path = root.join(".env");
+    upsert_env_file(&env_path, &env_var, &value)?;
     Ok(env_path.to_string_lossy().to_string())
 }
 
@@ -2388,68 +6577,96 @@ pub async fn env_upsert_key(project_path: String, env_var: String, value: String
 #[tauri::command]
 pub async fn env_upsert_key_at_path(path: String, env_var: String, value: String) -> Result<String, String> {
     let env_path = PathBuf::from(path);
-    if env_var.trim().is_empty() {
-        return Err("envVar is empty".to_string());
-    }
     let parent = env_path
         .parent()
         .ok_or_else(|| "env path has no parent directory".to_string())?;
     if !parent.is_dir() {
         return Err("env path parent is not a directory".to_string());
     }
+    upsert_env_file(&env_path, &env_var, &value)?;
+    Ok(env_path.to_string_lossy().to_string())
+}
 
-    let existing = std::fs::read_to_string(&env_path).unwrap_or_default();
-    let mut lines: Vec<String> = existing
-        .split('\n')
-        .map(|s| s.trim_end_matches('\r').to_string())
-        .collect();
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvKeyInfo {
+    pub key: String,
+    /// Last few characters of the value only, e.g. `••••abcd`.
+    pub masked_value: String,
+    pub line: usize,
+    /// True for repeats of a key; only the first occurrence takes effect.
+    pub shadowed: bool,
+}
 
-    let mut updated = false;
-    for line in lines.iter_mut() {
-        let raw = line.clone();
-        let trimmed_start = raw.trim_start();
-        if trimmed_start.starts_with('#') || trimmed_start.is_empty() {
-            continue;
-        }
+/// Accept either a `.env` file path or a directory containing one (e.g. a project root).
+fn resolve_env_file_arg(path: &str) -> Result<PathBuf, String> {
+    let p = PathBuf::from(path.trim());
+    if path.trim().is_empty() {
+        return Err("path is empty".to_string());
+    }
+    Ok(if p.is_dir() { p.join(".env") } else { p })
+}
 
-        let (export_prefix, rest) = if let Some(after) = trimmed_start.strip_prefix("export ") {
-            ("export ", after.trim_start())
-        } else {
-            ("", trimmed_start)
-        };
+fn mask_env_value(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.is_empty() {
+        return String::new();
+    }
+    // Short values would be fully revealed by their tail; show nothing.
+    if chars.len() <= 8 {
+        return "••••".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("••••{}", tail)
+}
+
+/// List keys in a `.env` file with masked values and line numbers (missing file = no keys).
+#[tauri::command]
+pub async fn env_list_keys(path: String) -> Result<Vec<EnvKeyInfo>, String> {
+    let env_path = resolve_env_file_arg(&path)?;
+    let text = match std::fs::read_to_string(&env_path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("read failed: {}", e)),
+    };
+    let mut seen = std::collections::HashSet::new();
+    Ok(crate::dotenv::EnvFile::parse(&text)
+        .entries()
+        .map(|e| EnvKeyInfo {
+            key: e.key.clone(),
+            masked_value: mask_env_value(&e.value),
+            line: e.line,
+            shadowed: !seen.insert(e.key.clone()),
+        })
+        .collect())
+}
 
-        if let Some(after_key) = rest.strip_prefix(env_var.as_str()) {
-            let after_key = after_key.trim_start();
-            if after_key.starts_with('=') {
-                let indent_len = raw.len().saturating_sub(trimmed_start.len());
-                let indent = &raw[..indent_len];
-                *line = format!(
-                    "{}{}{}={}",
-                    indent,
-                    export_prefix,
-                    env_var.trim(),
-                    format_env_value(value.trim())
-                );
-                updated = true;
-                break;
-            }
-        }
+/// Remove every occurrence of `key` from a `.env` file. Returns whether anything was removed.
+#[tauri::command]
+pub async fn env_delete_key(path: String, key: String) -> Result<bool, String> {
+    let env_path = resolve_env_file_arg(&path)?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("key is empty".to_string());
     }
-
-    if !updated {
-        if !lines.is_empty() && lines.last().is_some_and(|l| !l.is_empty()) {
-            lines.push(String::new());
-        }
-        lines.push(format!("{}={}", env_var.trim(), format_env_value(value.trim())));
+    if !env_path.is_file() {
+        return Ok(false);
     }
-
-    let mut out = lines.join("\n");
-    if !out.ends_with('\n') {
-        out.push('\n');
+    // No backup of the pre-delete contents: that copy would keep the secret being removed.
+    let removed = crate::persist::update_if(&env_path, crate::persist::Policy::TEXT_NO_BACKUP, |existing| {
+        let mut env = crate::dotenv::EnvFile::parse(&existing.unwrap_or_default());
+        Ok(env.remove(key).then(|| env.to_string()))
+    })?;
+    if removed {
+        // An older `.bak` (kept for `~/.snailer` env files) may still hold the key.
+        let bak = crate::persist::backup_path(&env_path);
+        crate::persist::update_if(&bak, crate::persist::Policy::TEXT_NO_BACKUP, |existing| {
+            let Some(text) = existing else { return Ok(None) };
+            let mut env = crate::dotenv::EnvFile::parse(&text);
+            Ok(env.remove(key).then(|| env.to_string()))
+        })?;
     }
-    std::fs::write(&env_path, out).map_err(|e| format!("write failed: {}", e))?;
-
-    Ok(env_path.to_string_lossy().to_string())
+    Ok(removed)
 }
 
 /// Ensure a line exists in `<projectPath>/.gitignore` (creates the file if missing).
@@ -2853,36 +7070,18 @@ fn load_system_ca_bundle() -> Option<Vec<u8>> {
 // xAI Direct API Call (CEO Auto-Cycle)
 // ============================================================================
 
+/// Read `key` from the active env profile (`~/.snailer/.env` unless another profile is active).
 fn read_env_key(key: &str) -> Result<String, String> {
-    let env_path = snailer_home_dir().join(".env");
+    let env_path = active_env_path()?;
+    let shown = display_snailer_path(&env_path);
     let contents = std::fs::read_to_string(&env_path)
-        .map_err(|e| format!("Failed to read ~/.snailer/.env: {}", e))?;
-
-    for line in contents.lines() {
-        let trimmed = line.trim();
-        if trimmed.is_empty() || trimmed.starts_with('#') {
-            continue;
-        }
-        if let Some(rest) = trimmed.strip_prefix(key) {
-            let rest = rest.trim_start();
-            if let Some(value) = rest.strip_prefix('=') {
-                let value = value.trim();
-                // Remove surrounding quotes if present
-                let value = if (value.starts_with('"') && value.ends_with('"'))
-                    || (value.starts_with('\'') && value.ends_with('\''))
-                {
-                    &value[1..value.len() - 1]
-                } else {
-                    value
-                };
-                if !value.is_empty() {
-                    return Ok(value.to_string());
-                }
-            }
-        }
-    }
+        .map_err(|e| format!("Failed to read {}: {}", shown, e))?;
 
-    Err(format!("{} not found in ~/.snailer/.env", key))
+    crate::dotenv::EnvFile::parse(&contents)
+        .get(key)
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| format!("{} not found in {}", key, shown))
 }
 
 /// Call xAI chat completions API directly with grok-4 model.
@@ -2891,7 +7090,7 @@ pub async fn xai_chat_completion(
     system_prompt: String,
     user_prompt: String,
 ) -> Result<LlmCompletionResponse, String> {
-    tauri::async_runtime::spawn_blocking(move || {
+    let resp = tauri::async_runtime::spawn_blocking(move || -> Result<LlmCompletionResponse, String> {
         let api_key = read_env_key("XAI_API_KEY")?;
         let model_name = "grok-4";
 
@@ -2944,10 +7143,13 @@ pub async fn xai_chat_completion(
             input_tokens,
             output_tokens,
             cached_input_tokens: 0,
+            web_search_calls: 0,
         })
     })
     .await
-    .map_err(|e| format!("xAI task failed: {}", e))?
+    .map_err(|e| format!("xAI task failed: {}", e))??;
+    record_completion_spend("xai", &resp);
+    Ok(resp)
 }
 
 /// Call OpenAI chat completions API directly with gpt-4o model (used by PM agent).
@@ -2956,7 +7158,7 @@ pub async fn openai_chat_completion(
     system_prompt: String,
     user_prompt: String,
 ) -> Result<LlmCompletionResponse, String> {
-    tauri::async_runtime::spawn_blocking(move || {
+    let resp = tauri::async_runtime::spawn_blocking(move || -> Result<LlmCompletionResponse, String> {
         let api_key = resolve_openai_bearer_for_gui()?;
         let model_name = "gpt-4o";
 
@@ -3014,10 +7216,13 @@ pub async fn openai_chat_completion(
             input_tokens,
             output_tokens,
             cached_input_tokens,
+            web_search_calls: 0,
         })
     })
     .await
-    .map_err(|e| format!("OpenAI task failed: {}", e))?
+    .map_err(|e| format!("OpenAI task failed: {}", e))??;
+    record_completion_spend("openai", &resp);
+    Ok(resp)
 }
 
 /// Call OpenAI GPT-5.2 via Responses API with reasoning support (used by QA agent).
@@ -3027,7 +7232,7 @@ pub async fn openai_gpt52_completion(
     user_prompt: String,
     reasoning_effort: Option<String>,
 ) -> Result<LlmCompletionResponse, String> {
-    tauri::async_runtime::spawn_blocking(move || {
+    let resp = tauri::async_runtime::spawn_blocking(move || -> Result<LlmCompletionResponse, String> {
         let api_key = resolve_openai_bearer_for_gui()?;
         let model_name = "gpt-5.2";
         let effort = reasoning_effort.unwrap_or_else(|| "medium".to_string());
@@ -3098,10 +7303,13 @@ pub async fn openai_gpt52_completion(
             input_tokens,
             output_tokens,
             cached_input_tokens,
+            web_search_calls: 0,
         })
     })
     .await
-    .map_err(|e| format!("OpenAI GPT-5.2 task failed: {}", e))?
+    .map_err(|e| format!("OpenAI GPT-5.2 task failed: {}", e))??;
+    record_completion_spend("openai", &resp);
+    Ok(resp)
 }
 
 /// Call Kimi chat completions API with built-in `$web_search` tool.
@@ -3114,7 +7322,7 @@ pub async fn kimi_web_search_completion(
     system_prompt: String,
     user_prompt: String,
 ) -> Result<LlmCompletionResponse, String> {
-    tauri::async_runtime::spawn_blocking(move || {
+    let resp = tauri::async_runtime::spawn_blocking(move || -> Result<LlmCompletionResponse, String> {
         let api_key = read_env_key("MOONSHOT_API_KEY")?;
 
         // Use MOONSHOT_API_BASE env var, default to global endpoint (api.moonshot.ai)
@@ -3145,6 +7353,7 @@ pub async fn kimi_web_search_completion(
         // Track cumulative token usage across iterations
         let mut total_input_tokens: u64 = 0;
         let mut total_output_tokens: u64 = 0;
+        let mut web_search_calls: u64 = 0;
 
         for _iter in 0..MAX_ITERATIONS {
             let body = serde_json::json!({
@@ -3203,6 +7412,7 @@ pub async fn kimi_web_search_completion(
                     input_tokens: total_input_tokens,
                     output_tokens: total_output_tokens,
                     cached_input_tokens: 0,
+                    web_search_calls,
                 });
             }
 
@@ -3224,6 +7434,9 @@ pub async fn kimi_web_search_completion(
                             .get("arguments")
                             .and_then(|a| a.as_str())
                             .unwrap_or("{}");
+                        if name == "$web_search" {
+                            web_search_calls += 1;
+                        }
 
                         messages.push(serde_json::json!({
                             "role": "tool",
@@ -3247,6 +7460,7 @@ pub async fn kimi_web_search_completion(
                     input_tokens: total_input_tokens,
                     output_tokens: total_output_tokens,
                     cached_input_tokens: 0,
+                    web_search_calls,
                 });
             }
 
@@ -3259,7 +7473,9 @@ pub async fn kimi_web_search_completion(
         Err("Kimi API: Max iterations reached without final response".to_string())
     })
     .await
-    .map_err(|e| format!("Task join error: {}", e))?
+    .map_err(|e| format!("Task join error: {}", e))??;
+    record_completion_spend("moonshot", &resp);
+    Ok(resp)
 }
 
 /// Call xAI Responses API with grok-4-1-fast model + web_search tool.
@@ -3269,7 +7485,7 @@ pub async fn xai_web_search_completion(
     system_prompt: String,
     user_prompt: String,
 ) -> Result<LlmCompletionResponse, String> {
-    tauri::async_runtime::spawn_blocking(move || {
+    let resp = tauri::async_runtime::spawn_blocking(move || -> Result<LlmCompletionResponse, String> {
         let api_key = read_env_key("XAI_API_KEY")?;
         let model_name = "grok-4-1-fast";
 
@@ -3305,6 +7521,12 @@ pub async fn xai_web_search_completion(
 
         // Extract text from output[].type=="message" → content[].type=="output_text" → .text
         let output = resp_json.get("output").and_then(|o| o.as_array());
+        let web_search_calls = output.map_or(0, |items| {
+            items
+                .iter()
+                .filter(|item| item.get("type").and_then(|t| t.as_str()) == Some("web_search_call"))
+                .count() as u64
+        });
         if let Some(items) = output {
             for item in items {
                 if item.get("type").and_then(|t| t.as_str()) == Some("message") {
@@ -3319,6 +7541,7 @@ pub async fn xai_web_search_completion(
                                             input_tokens,
                                             output_tokens,
                                             cached_input_tokens: 0,
+                                            web_search_calls,
                                         });
                                     }
                                 }
@@ -3335,7 +7558,9 @@ pub async fn xai_web_search_completion(
         ))
     })
     .await
-    .map_err(|e| format!("xAI web search task failed: {}", e))?
+    .map_err(|e| format!("xAI web search task failed: {}", e))??;
+    record_completion_spend("xai", &resp);
+    Ok(resp)
 }
 
 /// Call Anthropic Messages API directly (used by SWE/frontend/QA agents).
@@ -3345,7 +7570,7 @@ pub async fn anthropic_chat_completion(
     user_prompt: String,
     model: Option<String>,
 ) -> Result<LlmCompletionResponse, String> {
-    tauri::async_runtime::spawn_blocking(move || {
+    let resp = tauri::async_runtime::spawn_blocking(move || -> Result<LlmCompletionResponse, String> {
         let api_key = read_env_key("ANTHROPIC_API_KEY").or_else(|_| read_env_key("CLAUDE_API_KEY"))?;
         let model_name = model.unwrap_or_else(|| "claude-opus-4-6".to_string());
 
@@ -3398,10 +7623,13 @@ pub async fn anthropic_chat_completion(
             input_tokens,
             output_tokens,
             cached_input_tokens: 0,
+            web_search_calls: 0,
         })
     })
     .await
-    .map_err(|e| format!("Anthropic task failed: {}", e))?
+    .map_err(|e| format!("Anthropic task failed: {}", e))??;
+    record_completion_spend("anthropic", &resp);
+    Ok(resp)
 }
 
 // ============================================================================
@@ -4324,6 +8552,399 @@ pub async fn gh_issue_view_comments(
     Ok(result)
 }
 
+/// One row of the diagnostics checklist shown in the UI.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticCheck {
+    pub id: String,
+    pub label: String,
+    pub status: String, // "pass" | "warn" | "fail"
+    pub detail: String,
+}
+
+impl DiagnosticCheck {
+    fn new(id: &str, label: &str, status: &str, detail: impl Into<String>) -> Self {
+        Self {
+            id: id.to_string(),
+            label: label.to_string(),
+            status: status.to_string(),
+            detail: redact_secrets(&detail.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsReport {
+    pub zip_path: String,
+    pub generated_at: String,
+    pub checklist: Vec<DiagnosticCheck>,
+}
+
+const REDACTED: &str = "[redacted]";
+const SECRET_VALUE_PREFIXES: &[&str] = &[
+    "sk-", "xai-", "ghp_", "gho_", "ghu_", "ghs_", "github_pat_", "AIza", "eyJ", "npm_",
+];
+
+fn is_secret_key_name(key: &str) -> bool {
+    let k = key.to_ascii_lowercase();
+    ["token", "secret", "password", "apikey", "api_key", "authorization", "cookie", "email", "identity", "account_id", "accountid"]
+        .iter()
+        .any(|needle| k.contains(needle))
+        || k.ends_with("key")
+}
+
+fn redact_emails(text: &str) -> String {
+    let is_local = |c: char| c.is_ascii_alphanumeric() || "._%+-".contains(c);
+    let is_domain = |c: char| c.is_ascii_alphanumeric() || ".-".contains(c);
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '@' {
+            let mut start = i;
+            while start > 0 && is_local(chars[start - 1]) {
+                start -= 1;
+            }
+            let mut end = i + 1;
+            while end < chars.len() && is_domain(chars[end]) {
+                end += 1;
+            }
+            let domain: String = chars[i + 1..end].iter().collect();
+            let local_len = i - start;
+            if local_len > 0 && domain.trim_end_matches('.').contains('.') {
+                // Drop the local part already copied to `out`.
+                for _ in 0..local_len {
+                    out.pop();
+                }
+                out.push_str("[redacted-email]");
+                i = end;
+                continue;
+            }
+        }
+        out.push(chars[i]);
+        i += 1;
+    }
+    out
+}
+
+/// Redact the value of every `..._KEY=`, `..._TOKEN:`, `..._SECRET=` or `..._PASSWORD=`
+/// assignment anywhere in `line` (e.g. `loaded env: ANTHROPIC_API_KEY=sk-ant-...`).
+fn redact_inline_assignments(line: &str) -> String {
+    let is_name = |c: char| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_';
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    let mut prev: Option<char> = None;
+    while let Some(c) = rest.chars().next() {
+        let at_boundary = prev.map_or(true, |p| !(p.is_ascii_alphanumeric() || p == '_'));
+        if at_boundary && is_name(c) {
+            let name_len = rest.find(|c: char| !is_name(c)).unwrap_or(rest.len());
+            let name = &rest[..name_len];
+            let after = &rest[name_len..];
+            let value_start = ["KEY", "TOKEN", "SECRET", "PASSWORD"]
+                .iter()
+                .any(|s| name.ends_with(s))
+                .then(|| after.trim_start())
+                .and_then(|a| a.strip_prefix(['=', ':']))
+                .map(|a| a.trim_start())
+                .filter(|a| a.starts_with(|c: char| !c.is_whitespace()));
+            if let Some(value) = value_start {
+                let value_len = value.find(char::is_whitespace).unwrap_or(value.len());
+                out.push_str(&rest[..rest.len() - value.len()]);
+                out.push_str(REDACTED);
+                rest = &value[value_len..];
+                prev = REDACTED.chars().last();
+                continue;
+            }
+            out.push_str(name);
+            rest = after;
+            prev = name.chars().last();
+            continue;
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+        prev = Some(c);
+    }
+    out
+}
+
+/// Replace each known secret value (from env files and the keychain) wherever it appears.
+fn redact_known_values(text: &str, values: &[String]) -> String {
+    values
+        .iter()
+        .fold(text.to_string(), |acc, v| if v.is_empty() { acc } else { acc.replace(v.as_str(), REDACTED) })
+}
+
+/// Secret values this GUI can see: secret-named keys in the candidate env files plus tokens
+/// held in the keychain. Longest first, so a value containing another is replaced whole.
+fn known_secret_values(project_path: &str) -> Vec<String> {
+    let mut values: Vec<String> = Vec::new();
+    for (_, path) in env_candidate_files(project_path) {
+        if let Ok(secrets) = env_file_secrets(&path) {
+            values.extend(
+                secrets
+                    .into_iter()
+                    .filter(|(k, _)| is_secret_key_name(k))
+                    .map(|(_, v)| v.trim().to_string()),
+            );
+        }
+    }
+    values.extend(npm_token_keychain_get());
+    if let Ok(Some(auth)) = auth_keychain_get() {
+        values.push(auth.access_token);
+        values.push(auth.refresh_token);
+    }
+    if let Ok(Some((session, _))) = load_openai_oauth_session() {
+        values.push(session.access_token);
+        values.extend(session.refresh_token);
+        values.extend(session.id_token);
+    }
+    if let Ok(Some((key, _))) = load_openai_legacy_api_key() {
+        values.push(key);
+    }
+    // Short values (`true`, `1234`) would blank out unrelated text.
+    values.retain(|v| v.len() >= 8);
+    values.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    values.dedup();
+    values
+}
+
+/// Scrub API keys, bearer tokens, `KEY=value` secrets and email addresses from free text.
+fn redact_secrets(text: &str) -> String {
+    let mut lines = Vec::new();
+    for line in text.split('\n') {
+        // `OPENAI_API_KEY=...` / `token: ...` style assignments.
+        let assignment = line.find(['=', ':']).filter(|&idx| {
+            let key = line[..idx].trim().trim_start_matches("export ").trim_matches('"');
+            !key.is_empty() && !key.contains(' ') && is_secret_key_name(key)
+        });
+        let line = match assignment {
+            Some(idx) if !line[idx + 1..].trim().is_empty() => format!("{}{} {}", &line[..idx], &line[idx..=idx], REDACTED),
+            _ => line.to_string(),
+        };
+        let line = redact_inline_assignments(&line);
+
+        let mut words: Vec<String> = Vec::new();
+        let mut redact_next = false;
+        for word in line.split(' ') {
+            let bare = word.trim_matches(|c: char| "\"'`,;()[]{}<>".contains(c));
+            let lower = bare.to_ascii_lowercase();
+            let secret = redact_next
+                || (bare.len() >= 12 && SECRET_VALUE_PREFIXES.iter().any(|p| bare.starts_with(p)));
+            redact_next = lower == "bearer" || lower == "--token" || lower == "token";
+            words.push(if secret && !bare.is_empty() {
+                word.replace(bare, REDACTED)
+            } else {
+                word.to_string()
+            });
+        }
+        lines.push(words.join(" "));
+    }
+    redact_emails(&lines.join("\n"))
+}
+
+/// Redact a JSON document: secret-looking keys lose their values, every string is scrubbed.
+fn redact_json(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.iter()
+                .map(|(k, v)| {
+                    let v = match v {
+                        serde_json::Value::String(s) if is_secret_key_name(k) && !s.is_empty() => {
+                            serde_json::Value::String(REDACTED.to_string())
+                        }
+                        other => redact_json(other),
+                    };
+                    (k.clone(), v)
+                })
+                .collect(),
+        ),
+        serde_json::Value::Array(items) => serde_json::Value::Array(items.iter().map(redact_json).collect()),
+        serde_json::Value::String(s) => serde_json::Value::String(redact_secrets(s)),
+        other => other.clone(),
+    }
+}
+
+fn to_json_or_error<T: Serialize>(result: &Result<T, String>) -> serde_json::Value {
+    match result {
+        Ok(v) => serde_json::to_value(v).unwrap_or(serde_json::Value::Null),
+        Err(e) => serde_json::json!({ "error": e }),
+    }
+}
+
+/// TCP reachability of an `https://host:port` style address.
+fn probe_tcp_addr(addr: &str) -> Result<(), String> {
+    let url = Url::parse(addr).map_err(|e| format!("invalid address {}: {}", addr, e))?;
+    let host = url.host_str().ok_or_else(|| format!("address has no host: {}", addr))?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addrs = std::net::ToSocketAddrs::to_socket_addrs(&(host, port))
+        .map_err(|e| format!("DNS lookup for {} failed: {}", host, e))?;
+    let mut last = format!("no addresses for {}", host);
+    for a in addrs {
+        match std::net::TcpStream::connect_timeout(&a, Duration::from_secs(3)) {
+            Ok(_) => return Ok(()),
+            Err(e) => last = format!("connect to {} failed: {}", a, e),
+        }
+    }
+    Err(last)
+}
+
+/// Collect setup diagnostics into a redacted zip under `~/.snailer/diagnostics`
+/// and return a pass/warn/fail checklist for the UI.
+#[tauri::command]
+pub async fn diagnostics_collect(project_path: Option<String>) -> Result<DiagnosticsReport, String> {
+    let project_path = non_empty_trimmed(project_path);
+    let mut checklist = Vec::new();
+    let mut report = serde_json::Map::new();
+
+    let cli = snailer_cli_status().await;
+    match &cli {
+        Ok(st) if st.installed && st.compat.compatible => checklist.push(DiagnosticCheck::new(
+            "cli",
+            "Snailer CLI",
+            "pass",
+            format!("{} ({})", st.compat.installed.clone().unwrap_or_default(), st.install_source.clone().unwrap_or_default()),
+        )),
+        Ok(st) if !st.compat.compatible && st.compat.installed.is_some() => {
+            checklist.push(DiagnosticCheck::new("cli", "Snailer CLI", "fail", st.compat.error_message()))
+        }
+        Ok(_) => checklist.push(DiagnosticCheck::new("cli", "Snailer CLI", "fail", "not installed")),
+        Err(e) => checklist.push(DiagnosticCheck::new("cli", "Snailer CLI", "fail", e.clone())),
+    }
+    report.insert("cliStatus".to_string(), to_json_or_error(&cli));
+
+    let project_for_blocking = project_path.clone();
+    let (node, npm, github, engines_info, probe, auth_addr) = tauri::async_runtime::spawn_blocking(move || {
+        let bundled = snailer_node_current_bin_dir();
+        let extra = bundled.is_dir().then_some(bundled);
+        let version_of = |program: &str| {
+            let system = run_quick_cmd_stdout(Path::new(program), &["--version"], extra.as_deref())
+                .ok()
+                .flatten();
+            system.or_else(|| {
+                let bin = extra.as_ref()?.join(program);
+                run_quick_cmd_stdout(&bin, &["--version"], extra.as_deref()).ok().flatten()
+            })
+        };
+        let node = version_of("node");
+        let npm = version_of("npm");
+        let github = github_cli_status(project_for_blocking);
+        let engines_info = engine_list();
+        let auth_addr = resolve_auth_addr();
+        let probe = auth_addr.as_ref().map_err(|e| e.clone()).and_then(|a| probe_tcp_addr(a));
+        (node, npm, github, engines_info, probe, auth_addr)
+    })
+    .await
+    .map_err(|e| format!("diagnostics task failed: {}", e))?;
+
+    checklist.push(match (&node, &npm) {
+        (Some(n), Some(m)) => DiagnosticCheck::new("node", "Node.js / npm", "pass", format!("node {}, npm {}", n.trim(), m.trim())),
+        (Some(n), None) => DiagnosticCheck::new("node", "Node.js / npm", "fail", format!("node {} but npm is missing", n.trim())),
+        _ => DiagnosticCheck::new("node", "Node.js / npm", "warn", "not found; the GUI will install a private copy"),
+    });
+    report.insert("node".to_string(), serde_json::json!({ "node": node, "npm": npm }));
+
+    checklist.push(match &github {
+        Ok(g) if g.installed && g.auth == "ok" => DiagnosticCheck::new("github", "GitHub CLI", "pass", g.version.clone().unwrap_or_default()),
+        Ok(g) if g.installed => DiagnosticCheck::new("github", "GitHub CLI", "warn", format!("auth: {}", g.auth)),
+        Ok(_) => DiagnosticCheck::new("github", "GitHub CLI", "warn", "gh is not installed"),
+        Err(e) => DiagnosticCheck::new("github", "GitHub CLI", "warn", e.clone()),
+    });
+    report.insert("githubCli".to_string(), to_json_or_error(&github));
+
+    let env = env_find(project_path.clone().unwrap_or_default()).await;
+    checklist.push(match &env {
+        Ok(e) if e.found => DiagnosticCheck::new("env", ".env file", "pass", e.selected_path.clone().unwrap_or_default()),
+        Ok(_) => DiagnosticCheck::new("env", ".env file", "warn", "no .env file found"),
+        Err(e) => DiagnosticCheck::new("env", ".env file", "warn", e.clone()),
+    });
+    report.insert("envFind".to_string(), to_json_or_error(&env));
+
+    checklist.push(match (&auth_addr, &probe) {
+        (Ok(a), Ok(())) => DiagnosticCheck::new("auth", "Auth server", "pass", format!("{} reachable", a)),
+        (Ok(a), Err(e)) => DiagnosticCheck::new("auth", "Auth server", "fail", format!("{}: {}", a, e)),
+        (Err(e), _) => DiagnosticCheck::new("auth", "Auth server", "fail", e.clone()),
+    });
+    report.insert(
+        "auth".to_string(),
+        serde_json::json!({ "addr": to_json_or_error(&auth_addr), "probe": to_json_or_error(&probe) }),
+    );
+
+    let openai = openai_login_status().await;
+    checklist.push(match &openai {
+        Ok(o) if o.connected => DiagnosticCheck::new("openai", "OpenAI login", "pass", o.source.clone()),
+        Ok(o) => DiagnosticCheck::new("openai", "OpenAI login", "warn", o.status.clone()),
+        Err(e) => DiagnosticCheck::new("openai", "OpenAI login", "warn", e.clone()),
+    });
+    report.insert("openaiLogin".to_string(), to_json_or_error(&openai));
+
+    checklist.push(match &engines_info {
+        Ok(list) if !list.is_empty() => DiagnosticCheck::new("engine", "Engine", "pass", format!("{} running", list.len())),
+        Ok(_) => DiagnosticCheck::new("engine", "Engine", "warn", "no engine running"),
+        Err(e) => DiagnosticCheck::new("engine", "Engine", "fail", e.clone()),
+    });
+    report.insert("engines".to_string(), to_json_or_error(&engines_info));
+    report.insert(
+        "system".to_string(),
+        serde_json::json!({
+            "appVersion": env!("CARGO_PKG_VERSION"),
+            "os": std::env::consts::OS,
+            "arch": std::env::consts::ARCH,
+        }),
+    );
+
+    let mut engine_ids: Vec<String> = engines_info
+        .as_ref()
+        .map(|l| l.iter().map(|e| e.id.clone()).collect())
+        .unwrap_or_default();
+    if !engine_ids.iter().any(|id| id == DEFAULT_ENGINE_ID) {
+        engine_ids.push(DEFAULT_ENGINE_ID.to_string());
+    }
+
+    let generated_at = chrono::Utc::now();
+    let checklist_for_zip = checklist.clone();
+    let project_for_logs = project_path.clone();
+    let zip_path = tauri::async_runtime::spawn_blocking(move || -> Result<PathBuf, String> {
+        let dir = snailer_home_dir().join("diagnostics");
+        std::fs::create_dir_all(&dir).map_err(|e| format!("mkdir failed: {}", e))?;
+        let path = dir.join(format!("snailer-diagnostics-{}.zip", generated_at.format("%Y%m%d-%H%M%S")));
+        let file = std::fs::File::create(&path).map_err(|e| format!("create zip failed: {}", e))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        let mut add = |name: &str, body: &[u8]| -> Result<(), String> {
+            zip.start_file(name, options).map_err(|e| format!("zip failed: {}", e))?;
+            zip.write_all(body).map_err(|e| format!("zip write failed: {}", e))
+        };
+
+        let report = redact_json(&serde_json::Value::Object(report));
+        let pretty = |v: &serde_json::Value| serde_json::to_vec_pretty(v).unwrap_or_default();
+        add("report.json", &pretty(&report))?;
+        add("checklist.json", &pretty(&serde_json::to_value(&checklist_for_zip).unwrap_or_default()))?;
+        let known = known_secret_values(project_for_logs.as_deref().unwrap_or_default());
+        for id in engine_ids {
+            let lines = read_daemon_logs(&snailer_logs_dir(), &id, 1000, None);
+            let text: String = lines
+                .iter()
+                .map(|l| {
+                    let line = redact_known_values(&l.line, &known);
+                    format!("{} [{}] {}\n", l.ts, l.stream, redact_secrets(&line))
+                })
+                .collect();
+            add(&format!("logs/{}.log", daemon_log_stem(&id)), text.as_bytes())?;
+        }
+        zip.finish().map_err(|e| format!("zip finish failed: {}", e))?;
+        Ok(path)
+    })
+    .await
+    .map_err(|e| format!("diagnostics task failed: {}", e))??;
+
+    Ok(DiagnosticsReport {
+        zip_path: zip_path.to_string_lossy().to_string(),
+        generated_at: generated_at.to_rfc3339(),
+        checklist,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -4358,6 +8979,584 @@ mod tests {
         assert!(!candidates.iter().any(|a| a.contains("auth.snailer.dev")));
     }
 
+    #[test]
+    fn daemon_log_lines_round_trip_and_rotate() {
+        let dir = std::env::temp_dir().join(format!("snailer-logs-{}", uuid::Uuid::new_v4()));
+        let mut sink = DaemonLogSink::open(dir.clone(), DEFAULT_ENGINE_ID).expect("open sink");
+        for i in 0..3 {
+            sink.append(&EngineLogLine {
+                engine_id: DEFAULT_ENGINE_ID.to_string(),
+                ts: 1_700_000_000_000 + i,
+                stream: "stderr".to_string(),
+                line: format!("line {}", i),
+            });
+        }
+        sink.rotate();
+        sink.append(&EngineLogLine {
+            engine_id: DEFAULT_ENGINE_ID.to_string(),
+            ts: 1_700_000_000_010,
+            stream: "stdout".to_string(),
+            line: String::new(),
+        });
+
+        assert!(dir.join("daemon-1.log").is_file());
+        let all = read_daemon_logs(&dir, DEFAULT_ENGINE_ID, 10, None);
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[0].line, "line 0");
+        assert_eq!(all[3].stream, "stdout");
+        assert_eq!(all[3].line, "");
+
+        let tail = read_daemon_logs(&dir, DEFAULT_ENGINE_ID, 2, Some(1_700_000_000_001));
+        assert_eq!(tail.iter().map(|e| e.ts).collect::<Vec<_>>(), vec![1_700_000_000_002, 1_700_000_000_010]);
+        assert_eq!(sink.recent_output(0, 1), vec![String::new()]);
+        let mark = sink.appended;
+        assert!(sink.recent_output(mark, 20).is_empty());
+        sink.append(&EngineLogLine {
+            engine_id: DEFAULT_ENGINE_ID.to_string(),
+            ts: 1_700_000_000_011,
+            stream: "stderr".to_string(),
+            line: "after".to_string(),
+        });
+        assert_eq!(sink.recent_output(mark, 20), vec!["after".to_string()]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn engine_ids_prefer_explicit_id_then_project() {
+        let project = Path::new("/work/repo-a");
+        assert_eq!(resolve_engine_id(Some(" ci "), Some(project)), "ci");
+        assert_eq!(resolve_engine_id(Some(""), Some(project)), "/work/repo-a");
+        assert_eq!(resolve_engine_id(None, None), DEFAULT_ENGINE_ID);
+        assert_eq!(daemon_log_stem(DEFAULT_ENGINE_ID), "daemon");
+        let stem = daemon_log_stem("/work/repo-a");
+        assert!(stem.starts_with("daemon-repo-a-"));
+        assert_ne!(stem, daemon_log_stem("/other/repo-a"));
+    }
+
+    #[test]
+    fn cli_version_parsing_and_actions() {
+        assert_eq!(parse_cli_version("snailer v0.3.7\n"), Some(semver::Version::new(0, 3, 7)));
+        assert_eq!(parse_cli_version("garbage"), None);
+
+        let req = semver::VersionReq::parse(">=0.3.2, <0.5.0").unwrap();
+        assert_eq!(cli_version_action(&semver::Version::new(0, 3, 1), &req), "upgrade");
+        assert_eq!(cli_version_action(&semver::Version::new(0, 5, 0), &req), "downgrade");
+        assert_eq!(npm_version_spec(">=0.3.2, <0.5.0"), ">=0.3.2 <0.5.0");
+        assert!(semver::VersionReq::parse(DEFAULT_SNAILER_CLI_VERSION_REQ).is_ok());
+    }
+
+    #[test]
+    fn daemon_capabilities_accept_silent_daemons_and_reject_mismatches() {
+        assert!(check_daemon_capabilities(&serde_json::json!({})).is_ok());
+        assert!(check_daemon_capabilities(&serde_json::json!({ "protocolVersion": 2 })).is_ok());
+
+        let err = check_daemon_capabilities(&serde_json::json!({ "protocolVersion": "3" })).unwrap_err();
+        assert!(err.starts_with("engine_protocol_mismatch"));
+        assert!(err.contains("downgrade"));
+
+        let err = check_daemon_capabilities(&serde_json::json!({
+            "capabilities": { "methods": ["session.list", "run.start"] }
+        }))
+        .unwrap_err();
+        assert!(err.contains("approval.respond"));
+    }
+
+    #[test]
+    fn daemon_messages_split_into_responses_and_notifications() {
+        assert_eq!(
+            parse_daemon_message(r#"{"jsonrpc":"2.0","id":7,"result":{"sessions":[]}}"#),
+            Some(DaemonIncoming::Response {
+                id: 7,
+                result: Ok(serde_json::json!({ "sessions": [] })),
+            })
+        );
+        assert_eq!(
+            parse_daemon_message(r#"{"jsonrpc":"2.0","id":8,"error":{"code":-32601,"message":"nope"}}"#),
+            Some(DaemonIncoming::Response {
+                id: 8,
+                result: Err("nope".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_daemon_message(r#"{"jsonrpc":"2.0","method":"run.status","params":{"runId":"r1"}}"#),
+            Some(DaemonIncoming::Notification {
+                method: "run.status".to_string(),
+                params: serde_json::json!({ "runId": "r1" }),
+            })
+        );
+        assert_eq!(parse_daemon_message("not json"), None);
+    }
+
+    #[test]
+    fn engine_limits_flag_only_exceeded_thresholds() {
+        let stats = EngineStats {
+            engine_id: "default".to_string(),
+            pid: Some(42),
+            uptime_secs: 10,
+            rss_bytes: 3 * 1024 * 1024 * 1024,
+            cpu_percent: 12.0,
+            open_fds: None,
+            processes: Vec::new(),
+            warnings: Vec::new(),
+        };
+        let limits: EngineLimits =
+            serde_json::from_str(r#"{"maxRssMb":2048,"maxCpuPercent":50,"maxOpenFds":100}"#).unwrap();
+        assert_eq!(engine_limit_breaches(&stats, &limits), vec!["rss 3072 MB exceeds limit 2048 MB".to_string()]);
+        assert!(!limits.restart_on_breach);
+        assert!(engine_limit_breaches(&stats, &EngineLimits::default()).is_empty());
+    }
+
+    #[test]
+    fn node_archive_checksum_lookup_and_safe_extraction() {
+        let hash = "a".repeat(64);
+        let shasums = format!(
+            "{}  node-v20.11.1-linux-x64.tar.xz\n{}  node-v20.11.1-linux-x64.tar.gz\n",
+            "b".repeat(64),
+            hash
+        );
+        assert_eq!(expected_sha256_for(&shasums, "node-v20.11.1-linux-x64.tar.gz"), Some(hash));
+        assert_eq!(expected_sha256_for(&shasums, "node-v20.11.1-darwin-arm64.tar.gz"), None);
+
+        let dir = std::env::temp_dir().join(format!("snailer-node-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write_archive = |name: &str, entry: &[u8]| {
+            let path = dir.join(name);
+            let gz = flate2::write::GzEncoder::new(std::fs::File::create(&path).unwrap(), flate2::Compression::fast());
+            let mut builder = tar::Builder::new(gz);
+            let mut header = tar::Header::new_old();
+            header.as_old_mut().name[..entry.len()].copy_from_slice(entry);
+            header.set_size(2);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, &b"ok"[..]).unwrap();
+            builder.into_inner().unwrap().finish().unwrap();
+            path
+        };
+
+        let good = write_archive("good.tar.gz", b"node-v1/bin/node");
+        let out = dir.join("out");
+        extract_tar_gz(&good, &out).unwrap();
+        assert_eq!(std::fs::read(out.join("node-v1/bin/node")).unwrap(), b"ok");
+        assert_eq!(sha256_file(&good).unwrap().len(), 64);
+
+        let evil = write_archive("evil.tar.gz", b"node-v1/../../evil");
+        assert!(extract_tar_gz(&evil, &dir.join("out2")).is_err());
+        assert!(!dir.join("evil").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn offline_bundle_is_discovered_and_installs_without_the_registry() {
+        assert_eq!(
+            node_version_from_archive_name("node-v20.11.1-linux-x64.tar.gz", "linux-x64"),
+            Some("v20.11.1".to_string())
+        );
+        assert_eq!(node_version_from_archive_name("node-v20.11.1-darwin-arm64.tar.gz", "linux-x64"), None);
+
+        let dir = std::env::temp_dir().join(format!("snailer-bundle-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("npm-cache")).unwrap();
+        std::fs::write(dir.join("node-v20.11.1-linux-x64.tar.gz"), b"").unwrap();
+        std::fs::write(dir.join("node-v9.11.2-linux-x64.tar.gz"), b"").unwrap();
+        std::fs::write(dir.join("snailer-team-snailer-0.3.2.tgz"), b"").unwrap();
+        std::fs::write(dir.join("snailer-team-snailer-0.3.10.tgz"), b"").unwrap();
+        std::fs::write(dir.join("snailer-team-snailer-0.3.9.tgz"), b"").unwrap();
+        std::fs::write(dir.join("snailer-team-snailer-0.4.0.tgz"), b"").unwrap();
+        let bundle = scan_offline_bundle(&dir, "linux-x64", &semver::VersionReq::parse("^0.3").unwrap());
+        assert!(bundle.node_archive.as_ref().unwrap().ends_with("node-v20.11.1-linux-x64.tar.gz"));
+        assert!(bundle.cli_tgz.as_ref().unwrap().ends_with("snailer-team-snailer-0.3.10.tgz"));
+
+        let (args, source) = npm_install_args("^0.3", Some(&bundle));
+        assert_eq!(source, "offline-tgz");
+        assert!(args.contains(&"--offline".to_string()));
+        assert!(args.last().unwrap().ends_with("snailer-team-snailer-0.3.10.tgz"));
+
+        let (args, source) = npm_install_args("^0.3", None);
+        assert_eq!(source, "registry");
+        assert_eq!(args.last().unwrap(), "@snailer-team/snailer@^0.3");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn cli_channels_pick_targets_and_rollback_prefers_known_good() {
+        let versions: Vec<semver::Version> = ["0.2.9", "0.3.0", "0.3.4", "0.4.0", "0.4.1-beta.1"]
+            .iter()
+            .map(|v| semver::Version::parse(v).unwrap())
+            .collect();
+        let tags: HashMap<String, String> = [("latest", "0.4.0"), ("beta", "0.4.1-beta.1")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let req = semver::VersionReq::parse("^0.3").unwrap();
+        let target = |c: &str| cli_channel_target(c, &versions, &tags, &req).map(|v| v.to_string());
+        assert_eq!(target("pinned").as_deref(), Some("0.3.4"));
+        assert_eq!(target("stable").as_deref(), Some("0.4.0"));
+        assert_eq!(target("beta").as_deref(), Some("0.4.1-beta.1"));
+        assert!(resolve_cli_channel(Some("nightly".to_string()), &CliVersionsState::default()).is_err());
+
+        let mut state = CliVersionsState::default();
+        switch_cli_version(&mut state, "0.3.0");
+        switch_cli_version(&mut state, "0.3.4");
+        assert_eq!(state.previous.as_deref(), Some("0.3.0"));
+        assert_eq!(cli_rollback_candidates(&state, true), vec!["0.3.0".to_string()]);
+        state.current = Some("0.3.5".to_string());
+        state.previous = Some("0.3.4".to_string());
+        state.last_known_good = Some("0.3.0".to_string());
+        assert_eq!(cli_rollback_candidates(&state, false), vec!["0.3.0".to_string(), "0.3.4".to_string()]);
+    }
+
+    #[test]
+    fn install_download_copies_every_byte_without_an_app_handle() {
+        let data = vec![7u8; 600 * 1024];
+        let mut out = Vec::new();
+        let n = copy_with_progress(
+            &mut &data[..],
+            &mut out,
+            Some(data.len() as u64),
+            &InstallReporter::default(),
+            "node-download",
+        )
+        .unwrap();
+        assert_eq!(n, data.len() as u64);
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn npmrc_scopes_registries_and_keys_tokens_by_registry() {
+        let registry = normalize_registry_url("https://npm.corp.example/api/npm/").unwrap();
+        let scope = normalize_registry_url("https://npm.corp.example:8443/snailer").unwrap();
+        assert!(normalize_registry_url("file:///tmp/registry").is_err());
+        let text = render_npmrc(Some(&registry), Some(&scope), true);
+        assert_eq!(
+            text,
+            "registry=https://npm.corp.example/api/npm/\n\
+             @snailer-team:registry=https://npm.corp.example:8443/snailer/\n\
+             //npm.corp.example/api/npm/:_authToken=${SNAILER_NPM_REGISTRY_TOKEN}\n\
+             //npm.corp.example:8443/snailer/:_authToken=${SNAILER_NPM_REGISTRY_TOKEN}\n"
+        );
+        assert_eq!(render_npmrc(None, Some(&scope), false), "@snailer-team:registry=https://npm.corp.example:8443/snailer/\n");
+    }
+
+    #[test]
+    fn diagnostics_redact_keys_tokens_and_emails() {
+        let text = "OPENAI_API_KEY=sk-proj-abcdefghijklmnop\n\
+                    Authorization: Bearer abc.def.ghi\n\
+                    starting daemon --port 4100 --token 9f1c2d\n\
+                    logged in as jane.doe@example.com (ghp_0123456789abcdef)";
+        let out = redact_secrets(text);
+        assert!(!out.contains("sk-proj"));
+        assert!(!out.contains("abc.def.ghi"));
+        assert!(!out.contains("9f1c2d"));
+        assert!(!out.contains("jane.doe"));
+        assert!(!out.contains("ghp_0123"));
+        assert!(out.contains("--port 4100"));
+
+        let mid = redact_secrets("loaded env: ANTHROPIC_API_KEY=abc123secretvalue MOONSHOT_API_KEY = moon-9876 ok");
+        assert_eq!(mid, format!("loaded env: ANTHROPIC_API_KEY={} MOONSHOT_API_KEY = {} ok", REDACTED, REDACTED));
+        assert_eq!(redact_secrets("see MY_KEYBOARD=us"), "see MY_KEYBOARD=us");
+        let known = vec!["plain-looking-value-42".to_string()];
+        assert_eq!(
+            redact_known_values("echo plain-looking-value-42 done", &known),
+            format!("echo {} done", REDACTED)
+        );
+
+        let v = redact_json(&serde_json::json!({
+            "identity": "jane@example.com",
+            "hasRefreshToken": true,
+            "accessToken": "opaque",
+            "nested": [{ "path": "/home/jane/.env" }]
+        }));
+        assert_eq!(v["identity"], REDACTED);
+        assert_eq!(v["accessToken"], REDACTED);
+        assert_eq!(v["hasRefreshToken"], true);
+        assert_eq!(v["nested"][0]["path"], "/home/jane/.env");
+    }
+
+    #[test]
+    fn env_keys_are_listed_masked_and_deleted_everywhere() {
+        assert_eq!(mask_env_value(""), "");
+        assert_eq!(mask_env_value("short"), "••••");
+        assert_eq!(mask_env_value("xai-0123456789abcd"), "••••abcd");
+
+        let dir = std::env::temp_dir().join(format!("snailer-env-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.to_string_lossy().to_string();
+        std::fs::write(dir.join(".env"), "# keys\nXAI_API_KEY=xai-0123456789abcd\nOTHER=1\nXAI_API_KEY=old\n").unwrap();
+        std::fs::write(dir.join(".env.bak"), "XAI_API_KEY=xai-0123456789abcd\nOTHER=1\n").unwrap();
+
+        let keys = tauri::async_runtime::block_on(env_list_keys(path.clone())).unwrap();
+        let summary: Vec<(&str, usize, bool)> = keys.iter().map(|k| (k.key.as_str(), k.line, k.shadowed)).collect();
+        assert_eq!(summary, vec![("XAI_API_KEY", 2, false), ("OTHER", 3, false), ("XAI_API_KEY", 4, true)]);
+        assert_eq!(keys[0].masked_value, "••••abcd");
+
+        assert!(tauri::async_runtime::block_on(env_delete_key(path.clone(), "XAI_API_KEY".to_string())).unwrap());
+        assert_eq!(std::fs::read_to_string(dir.join(".env")).unwrap(), "# keys\nOTHER=1\n");
+        assert_eq!(std::fs::read_to_string(dir.join(".env.bak")).unwrap(), "OTHER=1\n");
+
+        // Nothing to remove: the file is left alone.
+        std::fs::write(dir.join(".env"), "OTHER=1").unwrap();
+        assert!(!tauri::async_runtime::block_on(env_delete_key(path, "XAI_API_KEY".to_string())).unwrap());
+        assert_eq!(std::fs::read_to_string(dir.join(".env")).unwrap(), "OTHER=1");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn env_profile_names_map_to_files_under_profiles() {
+        assert!(is_valid_env_profile_name("work-prod_2"));
+        assert!(!is_valid_env_profile_name(""));
+        assert!(!is_valid_env_profile_name("../escape"));
+        assert!(!is_valid_env_profile_name("a b"));
+        assert!(env_profile_path("../escape").is_err());
+        assert_eq!(env_profile_path("staging").unwrap(), env_profiles_dir().join("staging.env"));
+        assert_eq!(
+            display_snailer_path(&env_profiles_dir().join("staging.env")),
+            "~/.snailer/profiles/staging.env"
+        );
+    }
+
+    #[test]
+    fn provider_key_errors_are_classified_and_account_info_extracted() {
+        assert_eq!(classify_provider_http_error(401, "{\"error\":\"invalid x-api-key\"}"), "invalid");
+        assert_eq!(classify_provider_http_error(429, ""), "quota-exceeded");
+        assert_eq!(
+            classify_provider_http_error(403, "Your team has run out of credits"),
+            "quota-exceeded"
+        );
+        assert_eq!(classify_provider_http_error(400, "API key not valid. Please pass a valid API key."), "invalid");
+        assert_eq!(
+            classify_provider_http_error(400, "{\"error\":\"Incorrect API key provided: xai-****\"}"),
+            "invalid"
+        );
+        assert_eq!(classify_provider_http_error(400, "{\"error\":\"model not found\"}"), "http-error");
+        assert_eq!(classify_provider_http_error(503, "upstream"), "http-error");
+        assert!(provider_key_env_vars("claude").unwrap().contains(&"CLAUDE_API_KEY"));
+        assert!(provider_key_env_vars("mistral").is_none());
+
+        let xai = provider_account_info("xai", None, &serde_json::json!({ "name": "ci key", "team_id": "team-1" }));
+        assert_eq!(xai.organization.as_deref(), Some("team-1"));
+        assert_eq!(xai.name.as_deref(), Some("ci key"));
+        let kimi = provider_account_info("moonshot", None, &serde_json::json!({ "data": { "available_balance": 12.5 } }));
+        assert_eq!(kimi.balance, Some(12.5));
+        let openai = provider_account_info("openai", Some("org-abc".to_string()), &serde_json::Value::Null);
+        assert_eq!(openai.organization.as_deref(), Some("org-abc"));
+    }
+
+    #[test]
+    fn settings_diff_reports_changed_keys_with_masked_values() {
+        let dir = std::env::temp_dir().join(format!("snailer-watch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let env = dir.join(".env");
+        std::fs::write(&env, "XAI_API_KEY=xai-0123456789abcd\nKEEP=1\nGONE=x\n").unwrap();
+        let before = settings_snapshot("env", &env);
+        std::fs::write(&env, "XAI_API_KEY=xai-9999999999wxyz\nKEEP=1\nNEW=value-long-enough\n").unwrap();
+        let after = settings_snapshot("env", &env);
+        let changes: Vec<(String, String, Option<serde_json::Value>)> = diff_settings("env", &before, &after)
+            .into_iter()
+            .map(|c| (c.key, c.change, c.value))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("NEW".to_string(), "added".to_string(), Some(serde_json::json!("••••ough"))),
+                ("XAI_API_KEY".to_string(), "modified".to_string(), Some(serde_json::json!("••••wxyz"))),
+                ("GONE".to_string(), "removed".to_string(), None),
+            ]
+        );
+
+        let old: SettingsSnapshot = [("accessToken".to_string(), serde_json::json!("a"))].into_iter().collect();
+        let new: SettingsSnapshot = [("accessToken".to_string(), serde_json::json!("b"))].into_iter().collect();
+        assert_eq!(diff_settings("guiSettings", &old, &new)[0].value, Some(serde_json::json!(REDACTED)));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn env_resolution_reports_winner_shadowing_and_process_overrides() {
+        let dir = std::env::temp_dir().join(format!("snailer-resolve-{}", uuid::Uuid::new_v4()));
+        let project = dir.join("repo").join("app");
+        std::fs::create_dir_all(&project).unwrap();
+        let shared = dir.join("shared.env");
+        std::fs::write(&shared, "OPENAI_API_KEY=sk-shared-0123456789\nXAI_API_KEY=xai-shared\n").unwrap();
+        std::fs::write(project.join(".env"), "# app\nOPENAI_API_KEY=sk-project-abcdefgh\n").unwrap();
+        std::fs::write(dir.join("repo").join(".env"), "XAI_API_KEY=xai-repo\nPATH=/nowhere\n").unwrap();
+
+        assert_eq!(env_files_in_ancestors(&project).len(), 2);
+        let files = vec![
+            ("projectPath".to_string(), project.join(".env")),
+            ("projectAncestor".to_string(), dir.join("repo").join(".env")),
+            ("SNAILER_ENV_FILE".to_string(), shared.clone()),
+        ];
+        let keys = resolve_env_keys(&files, |k| k == "PATH");
+        let by_key = |k: &str| keys.iter().find(|r| r.key == k).unwrap();
+
+        let openai = by_key("OPENAI_API_KEY");
+        assert_eq!(openai.winner, project.join(".env").to_string_lossy());
+        assert_eq!(openai.line, 2);
+        assert_eq!(openai.masked_value, "••••efgh");
+        assert_eq!(openai.shadowed_in, vec![shared.to_string_lossy().to_string()]);
+        assert_eq!(by_key("XAI_API_KEY").winner, dir.join("repo").join(".env").to_string_lossy());
+        assert!(by_key("PATH").process_override);
+        assert!(!openai.process_override);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn config_bundle_paths_are_portable_across_home_directories() {
+        let inside = home_dir().join(".snailer").join(".env");
+        let portable = portable_home_path(&inside.to_string_lossy());
+        assert_eq!(portable, "~/.snailer/.env");
+        assert_eq!(PathBuf::from(expand_home_path(&portable)), inside);
+        assert_eq!(portable_home_path("/opt/bundles"), "/opt/bundles");
+        assert_eq!(expand_home_path("/opt/bundles"), "/opt/bundles");
+    }
+
+    #[test]
+    fn budget_rollover_keeps_caps_and_breakdown_flags_exceeded_caps() {
+        use chrono::TimeZone;
+
+        let day1 = chrono::Utc.with_ymd_and_hms(2026, 3, 30, 12, 0, 0).unwrap();
+        let mut state = new_budget_state(day1, 15.0, 13.0);
+        set_budget_cap(&mut state.agent_limits, "pm".to_string(), budget_cap(Some(1.0), Some(20.0)).unwrap());
+        set_budget_cap(&mut state.provider_limits, "xai".to_string(), budget_cap(None, Some(2.0)).unwrap());
+        state.agent_spend.insert("pm".to_string(), BudgetSpend { spent_today: 1.5, spent_month: 4.0 });
+        state.provider_spend.insert("xai".to_string(), BudgetSpend { spent_today: 1.5, spent_month: 2.5 });
+        state.agent_spend.insert("coder".to_string(), BudgetSpend { spent_today: 0.5, spent_month: 0.5 });
+
+        let agents = budget_breakdown(&state.agent_limits, &state.agent_spend);
+        assert_eq!(agents.iter().map(|b| b.id.as_str()).collect::<Vec<_>>(), vec!["coder", "pm"]);
+        assert_eq!(agents[0].exceeded, None);
+        assert_eq!(agents[1].exceeded.as_deref(), Some("daily"));
+        assert_eq!(budget_breakdown(&state.provider_limits, &state.provider_spend)[0].exceeded.as_deref(), Some("monthly"));
+
+        roll_budget_state(&mut state, day1 + chrono::Duration::days(1));
+        assert_eq!(state.agent_spend["pm"], BudgetSpend { spent_today: 0.0, spent_month: 4.0 });
+        roll_budget_state(&mut state, day1 + chrono::Duration::days(2));
+        assert!(state.agent_spend.is_empty() && state.provider_spend.is_empty());
+        assert_eq!(state.agent_limits["pm"].daily_limit, Some(1.0));
+        assert_eq!(state.month, 4);
+
+        assert!(budget_cap(Some(-1.0), None).is_err());
+        set_budget_cap(&mut state.agent_limits, "pm".to_string(), budget_cap(None, None).unwrap());
+        assert!(!state.agent_limits.contains_key("pm"));
+    }
+
+    #[test]
+    fn settings_validation_only_checks_patched_keys() {
+        let mut s = GuiSettings {
+            offline_bundle_dir: Some("/definitely/not/here".to_string()),
+            ..Default::default()
+        };
+        let patch = |v: serde_json::Value| v.as_object().cloned().unwrap();
+        validate_gui_settings(&mut s, &patch(serde_json::json!({ "authAddr": "x" }))).unwrap();
+        assert!(validate_gui_settings(&mut s, &patch(serde_json::json!({ "offlineBundleDir": "x" })))
+            .unwrap_err()
+            .contains("offlineBundleDir"));
+    }
+
+    #[test]
+    fn cli_updates_never_downgrade_unless_switching_channel() {
+        let v = |s: &str| semver::Version::parse(s).unwrap();
+        assert!(cli_update_available(Some(&v("0.3.1")), Some(&v("0.3.2")), false));
+        assert!(!cli_update_available(Some(&v("0.3.2")), Some(&v("0.3.1")), false));
+        assert!(cli_update_available(Some(&v("0.4.0-beta.1")), Some(&v("0.3.9")), true));
+        assert!(!cli_update_available(Some(&v("0.3.2")), Some(&v("0.3.2")), true));
+        assert!(cli_update_available(None, Some(&v("0.3.2")), false));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn engine_process_tree_does_not_count_threads_as_children() {
+        let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let stop = stop.clone();
+                std::thread::spawn(move || {
+                    while !stop.load(std::sync::atomic::Ordering::Relaxed) {
+                        std::thread::sleep(Duration::from_millis(5));
+                    }
+                })
+            })
+            .collect();
+
+        // A dedicated child, so children spawned by tests running in parallel don't show up
+        // in the tree being asserted on.
+        let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        let child_pid = child.id();
+
+        // Include tasks so thread entries are present and have to be filtered out.
+        let mut system = sysinfo::System::new();
+        system.refresh_processes_specifics(
+            sysinfo::ProcessesToUpdate::All,
+            true,
+            sysinfo::ProcessRefreshKind::nothing().with_memory(),
+        );
+        let children = process_children(&system);
+        let tree = process_tree(&system, &children, child_pid);
+        let own_tree = process_tree(&system, &children, std::process::id());
+        stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        for w in workers {
+            w.join().unwrap();
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+
+        assert_eq!(tree.len(), 1, "unexpected children: {:?}", tree);
+        assert_eq!(tree[0].pid, child_pid);
+        let rss = system.process(sysinfo::Pid::from_u32(child_pid)).unwrap().memory();
+        assert_eq!(tree[0].rss_bytes, rss);
+        // Our own worker threads must not appear as children of this process.
+        assert!(own_tree.iter().any(|p| p.pid == child_pid));
+        assert!(
+            own_tree.iter().all(|p| system
+                .process(sysinfo::Pid::from_u32(p.pid))
+                .is_some_and(|proc_| proc_.thread_kind().is_none())),
+            "threads were listed as children: {:?}",
+            own_tree
+        );
+    }
+
+    #[test]
+    fn cli_versions_used_by_running_engines_are_not_pruned() {
+        let id = format!("prune-test-{}", uuid::Uuid::new_v4());
+        let bin = snailer_cli_bin_path(&snailer_cli_versions_dir().join("0.3.7"));
+        engines().lock().unwrap().insert(
+            id.clone(),
+            EngineState {
+                id: id.clone(),
+                project_path: String::new(),
+                url: String::new(),
+                port: 0,
+                token: String::new(),
+                env_file: None,
+                cli_bin: bin.to_string_lossy().to_string(),
+                started_at: 0,
+                child: None,
+                rpc: None,
+            },
+        );
+        let in_use = cli_versions_in_use();
+        engines().lock().unwrap().remove(&id);
+        assert!(in_use.contains(&"0.3.7".to_string()));
+    }
+
+    #[test]
+    fn daemon_startup_detects_port_races_and_honours_explicit_timeout() {
+        let raced = vec![
+            "[stderr] Error: listen EADDRINUSE: address already in use 127.0.0.1:41234".to_string(),
+        ];
+        assert!(is_port_conflict_output(&raced));
+        assert!(!is_port_conflict_output(&["[stderr] invalid token".to_string()]));
+        assert_eq!(daemon_startup_timeout(Some(90)), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn orphan_reaper_only_matches_our_daemon_command_line() {
+        assert!(looks_like_snailer_daemon(
+            "node /home/u/.snailer/npm_cli/node_modules/.bin/snailer daemon --port 4100 --token abc",
+            4100
+        ));
+        assert!(!looks_like_snailer_daemon("node server.js --port 4100", 4100));
+        assert!(!looks_like_snailer_daemon("snailer daemon --port 41000 --token abc", 4100));
+    }
+
     #[test]
     fn non_empty_trimmed_drops_empty_values() {
         assert_eq!(non_empty_trimmed(None), None);
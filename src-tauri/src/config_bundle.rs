@@ -0,0 +1,240 @@
+//! Portable configuration bundles (`config_export` / `config_import`).
+//!
+//! A bundle is one JSON file holding GUI settings, budget limits and an opaque `frontend`
+//! section (router/orchestrator defaults owned by the UI). Secrets (env keys, the npm registry
+//! token) are only included when a passphrase is given, encrypted with ChaCha20-Poly1305 under
+//! an Argon2id-derived key. Without a passphrase they are left out entirely.
+
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::ChaCha20Poly1305;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+pub const FORMAT: &str = "snailer-config";
+pub const VERSION: u32 = 1;
+
+/// Upper bounds on the KDF cost read from a bundle, checked before any work is done so a
+/// crafted file can't demand gigabytes of memory or minutes of CPU. Generous relative to the
+/// argon2 defaults `encrypt_secrets` writes (19 MiB, 2 passes, 1 lane).
+const MAX_M_COST: u32 = 64 * 1024;
+const MAX_T_COST: u32 = 10;
+const MAX_P_COST: u32 = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigBundle {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    pub app_version: String,
+    pub gui_settings: Map<String, Value>,
+    pub budget: Option<BudgetLimits>,
+    #[serde(default)]
+    pub frontend: Option<Value>,
+    #[serde(default)]
+    pub secrets: Option<EncryptedSecrets>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetLimits {
+    /// The exporting machine's effective main/MiniMax limits. Informational: they follow
+    /// `SNAILER_PLAN` and the `SNAILER_BUDGET_*` env vars, so import reports but doesn't apply them.
+    pub monthly_limit_main: f32,
+    pub monthly_limit_minimax: f32,
+    /// Per-agent caps by agent id. `None` in bundles from builds without per-agent caps, which
+    /// leaves the local caps alone on import.
+    #[serde(default)]
+    pub agent_limits: Option<BTreeMap<String, BudgetCap>>,
+    /// Per-provider caps by provider id; `None` as for `agent_limits`.
+    #[serde(default)]
+    pub provider_limits: Option<BTreeMap<String, BudgetCap>>,
+}
+
+/// Daily and monthly caps in USD; `None` means uncapped.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetCap {
+    pub daily_limit: Option<f32>,
+    pub monthly_limit: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Secrets {
+    /// Keys from the active env file.
+    pub env: BTreeMap<String, String>,
+    pub npm_registry_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptedSecrets {
+    pub kdf: String,
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// One line of the import preview.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigChange {
+    pub section: String,
+    pub key: String,
+    pub from: Option<Value>,
+    pub to: Option<Value>,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], m_cost: u32, t_cost: u32, p_cost: u32) -> Result<[u8; 32], String> {
+    let params = argon2::Params::new(m_cost, t_cost, p_cost, Some(32)).map_err(|e| format!("invalid KDF parameters: {}", e))?;
+    let mut key = [0u8; 32];
+    argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+pub fn encrypt_secrets(secrets: &Secrets, passphrase: &str) -> Result<EncryptedSecrets, String> {
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let (m_cost, t_cost, p_cost) = (
+        argon2::Params::DEFAULT_M_COST,
+        argon2::Params::DEFAULT_T_COST,
+        argon2::Params::DEFAULT_P_COST,
+    );
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt, m_cost, t_cost, p_cost)?;
+    let cipher = ChaCha20Poly1305::new(&key.into());
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let plaintext = serde_json::to_vec(secrets).map_err(|e| format!("serialize failed: {}", e))?;
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_ref())
+        .map_err(|_| "encryption failed".to_string())?;
+    Ok(EncryptedSecrets {
+        kdf: "argon2id".to_string(),
+        m_cost,
+        t_cost,
+        p_cost,
+        salt: b64.encode(salt),
+        nonce: b64.encode(nonce),
+        ciphertext: b64.encode(ciphertext),
+    })
+}
+
+pub fn decrypt_secrets(enc: &EncryptedSecrets, passphrase: &str) -> Result<Secrets, String> {
+    let b64 = base64::engine::general_purpose::STANDARD;
+    if enc.kdf != "argon2id" {
+        return Err(format!("unsupported key derivation: {}", enc.kdf));
+    }
+    if enc.m_cost > MAX_M_COST || enc.t_cost > MAX_T_COST || enc.p_cost > MAX_P_COST {
+        return Err("secrets use key derivation parameters outside the supported range".to_string());
+    }
+    let decode = |field: &str, v: &str| b64.decode(v).map_err(|e| format!("invalid secrets.{}: {}", field, e));
+    let salt = decode("salt", &enc.salt)?;
+    let nonce = decode("nonce", &enc.nonce)?;
+    let ciphertext = decode("ciphertext", &enc.ciphertext)?;
+    if nonce.len() != 12 {
+        return Err("invalid secrets.nonce length".to_string());
+    }
+    let key = derive_key(passphrase, &salt, enc.m_cost, enc.t_cost, enc.p_cost)?;
+    let plaintext = ChaCha20Poly1305::new(&key.into())
+        .decrypt(nonce.as_slice().into(), ciphertext.as_ref())
+        .map_err(|_| "wrong passphrase or corrupted secrets".to_string())?;
+    serde_json::from_slice(&plaintext).map_err(|e| format!("invalid secrets payload: {}", e))
+}
+
+/// Parse a bundle and check it's one we can read.
+pub fn parse(text: &str) -> Result<ConfigBundle, String> {
+    let bundle: ConfigBundle = serde_json::from_str(text).map_err(|e| format!("not a valid config bundle: {}", e))?;
+    if bundle.format != FORMAT {
+        return Err(format!("not a Snailer config bundle (format '{}')", bundle.format));
+    }
+    if bundle.version > VERSION {
+        return Err(format!(
+            "bundle version {} is newer than this app supports ({}); update Snailer",
+            bundle.version, VERSION
+        ));
+    }
+    Ok(bundle)
+}
+
+/// Key-level differences between two JSON objects, in key order.
+pub fn diff_objects(section: &str, from: &Map<String, Value>, to: &Map<String, Value>) -> Vec<ConfigChange> {
+    let keys: std::collections::BTreeSet<&String> = from.keys().chain(to.keys()).collect();
+    keys.into_iter()
+        .filter(|k| from.get(*k) != to.get(*k))
+        .map(|k| ConfigChange {
+            section: section.to_string(),
+            key: k.clone(),
+            from: from.get(k).cloned(),
+            to: to.get(k).cloned(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_round_trip_only_with_the_right_passphrase() {
+        let secrets = Secrets {
+            env: [("XAI_API_KEY".to_string(), "xai-secret".to_string())].into_iter().collect(),
+            npm_registry_token: Some("npm_abc".to_string()),
+        };
+        let enc = encrypt_secrets(&secrets, "correct horse").unwrap();
+        assert!(!enc.ciphertext.contains("xai-secret"));
+        assert_eq!(decrypt_secrets(&enc, "correct horse").unwrap(), secrets);
+        assert!(decrypt_secrets(&enc, "wrong").unwrap_err().contains("wrong passphrase"));
+        let greedy = EncryptedSecrets {
+            m_cost: 4 * 1024 * 1024,
+            ..enc.clone()
+        };
+        assert!(decrypt_secrets(&greedy, "correct horse").unwrap_err().contains("outside the supported range"));
+
+        assert!(parse(r#"{"format":"other","version":1,"exportedAt":"","appVersion":"","guiSettings":{},"budget":null}"#)
+            .unwrap_err()
+            .contains("not a Snailer"));
+        let budget = BudgetLimits {
+            monthly_limit_main: 30.0,
+            monthly_limit_minimax: 13.0,
+            agent_limits: Some(
+                [("pm".to_string(), BudgetCap { daily_limit: Some(1.0), monthly_limit: None })].into_iter().collect(),
+            ),
+            provider_limits: Some(
+                [("xai".to_string(), BudgetCap { daily_limit: None, monthly_limit: Some(20.0) })].into_iter().collect(),
+            ),
+        };
+        let bundle = ConfigBundle {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            exported_at: String::new(),
+            app_version: String::new(),
+            gui_settings: Map::new(),
+            budget: Some(budget.clone()),
+            frontend: None,
+            secrets: Some(enc.clone()),
+        };
+        let parsed = parse(&serde_json::to_string(&bundle).unwrap()).unwrap();
+        assert_eq!(parsed.budget, Some(budget));
+        assert_eq!(decrypt_secrets(parsed.secrets.as_ref().unwrap(), "correct horse").unwrap(), secrets);
+        let older = parse(
+            r#"{"format":"snailer-config","version":1,"exportedAt":"","appVersion":"","guiSettings":{},
+                "budget":{"monthlyLimitMain":15,"monthlyLimitMinimax":13}}"#,
+        )
+        .unwrap();
+        assert_eq!(older.budget.unwrap().agent_limits, None);
+
+        let from: Map<String, Value> = serde_json::from_str(r#"{"a":1,"b":2}"#).unwrap();
+        let to: Map<String, Value> = serde_json::from_str(r#"{"b":3,"c":4}"#).unwrap();
+        let keys: Vec<String> = diff_objects("guiSettings", &from, &to).into_iter().map(|c| c.key).collect();
+        assert_eq!(keys, vec!["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,429 @@
+//! Lossless `.env` parsing and editing.
+//!
+//! A file is kept as a list of items: entries (`KEY=value`) and everything else (blank lines,
+//! comments, lines we can't parse) verbatim. Unmodified items are written back byte-for-byte,
+//! so editing one key never disturbs unrelated lines.
+//!
+//! Supported syntax:
+//! - optional `export ` prefix and leading indentation
+//! - unquoted values, with ` # comment` stripped (a `#` not preceded by whitespace is kept)
+//! - `"double"` quotes with `\n`, `\r`, `\t`, `\"` and `\\` escapes; may span lines
+//! - `'single'` and `` `backtick` `` quotes, taken literally; may span lines
+//! - a trailing ` # comment` after a quoted value
+//!
+//! When a key appears more than once, the first occurrence wins, matching the CLI's loader.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub value: String,
+    /// 1-based line number where the entry starts.
+    pub line: usize,
+    exported: bool,
+    indent: String,
+    /// Inline comment after the value, including its leading whitespace.
+    comment: Option<String>,
+    /// Line ending of the entry's last line (`"\n"`, `"\r\n"` or `""` at EOF).
+    eol: String,
+    /// Original text, used verbatim until the entry is modified.
+    raw: Option<String>,
+}
+
+impl Entry {
+    fn render(&self) -> String {
+        if let Some(raw) = self.raw.as_ref() {
+            return raw.clone();
+        }
+        format!(
+            "{}{}{}={}{}{}",
+            self.indent,
+            if self.exported { "export " } else { "" },
+            self.key,
+            format_value(&self.value),
+            self.comment.as_deref().unwrap_or(""),
+            self.eol
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    Raw(String),
+    Entry(Entry),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnvFile {
+    items: Vec<Item>,
+}
+
+/// Valid variable names: `[A-Za-z_][A-Za-z0-9_.]*`.
+pub fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Serialize a value so that parsing it back yields exactly `value`.
+///
+/// Simple values stay unquoted; anything with whitespace, quotes, `#`, `\` or control
+/// characters is double-quoted with escapes.
+pub fn format_value(value: &str) -> String {
+    let needs_quotes = value
+        .chars()
+        .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '#' | '"' | '\'' | '`' | '\\'));
+    if !needs_quotes {
+        return value.to_string();
+    }
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn split_eol(line: &str) -> (&str, &str) {
+    if let Some(body) = line.strip_suffix("\r\n") {
+        (body, "\r\n")
+    } else if let Some(body) = line.strip_suffix('\n') {
+        (body, "\n")
+    } else {
+        (line, "")
+    }
+}
+
+/// Parse the part after `=`. `first_eol` is the line ending of the first line and `rest` the
+/// following lines (with endings), consumed as needed by multi-line quoted values. Returns the
+/// value, inline comment and number of extra lines consumed.
+fn parse_value(first: &str, first_eol: &str, rest: &[&str]) -> Option<(String, Option<String>, usize)> {
+    let quote = first.chars().next().filter(|c| matches!(c, '"' | '\'' | '`'));
+    let Some(quote) = quote else {
+        // Unquoted: value runs to a whitespace-preceded `#`. `first` is already trimmed, so a
+        // `#` at the very start (`KEY= # note`) is a comment on an empty value.
+        let mut end = first.len();
+        let bytes = first.as_bytes();
+        for (i, b) in bytes.iter().enumerate() {
+            if *b == b'#' && (i == 0 || bytes[i - 1].is_ascii_whitespace()) {
+                end = i;
+                break;
+            }
+        }
+        let value = first[..end].trim_end();
+        let comment = (end < first.len()).then(|| match end {
+            // Keep the comment separated from a value set later.
+            0 => format!(" {}", first),
+            _ => first[value.len()..].to_string(),
+        });
+        return Some((value.to_string(), comment, 0));
+    };
+
+    let mut value = String::new();
+    let mut consumed = 0;
+    let mut current = &first[1..];
+    loop {
+        let mut chars = current.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c == quote {
+                let after = &current[i + 1..];
+                let trimmed = after.trim_start();
+                if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                    return None;
+                }
+                let comment = (!after.is_empty()).then(|| after.to_string());
+                return Some((value, comment, consumed));
+            }
+            if c == '\\' && quote == '"' {
+                match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, '"')) => value.push('"'),
+                    Some((_, '\\')) => value.push('\\'),
+                    Some((_, other)) => {
+                        value.push('\\');
+                        value.push(other);
+                    }
+                    None => value.push('\\'),
+                }
+                continue;
+            }
+            value.push(c);
+        }
+        // No closing quote on this line: continue onto the next one, keeping its line break.
+        let prev_eol = if consumed == 0 { first_eol } else { split_eol(rest[consumed - 1]).1 };
+        if prev_eol.is_empty() {
+            return None;
+        }
+        let next = rest.get(consumed)?;
+        value.push_str(prev_eol);
+        consumed += 1;
+        current = split_eol(next).0;
+    }
+}
+
+impl EnvFile {
+    pub fn parse(text: &str) -> Self {
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        let mut items = Vec::new();
+        let mut i = 0;
+        while i < lines.len() {
+            match Self::parse_entry(&lines, i) {
+                Some((entry, used)) => {
+                    items.push(Item::Entry(entry));
+                    i += used;
+                }
+                None => {
+                    items.push(Item::Raw(lines[i].to_string()));
+                    i += 1;
+                }
+            }
+        }
+        Self { items }
+    }
+
+    fn parse_entry(lines: &[&str], start: usize) -> Option<(Entry, usize)> {
+        let (body, first_eol) = split_eol(lines[start]);
+        let trimmed = body.trim_start();
+        let indent = &body[..body.len() - trimmed.len()];
+        if trimmed.starts_with('#') {
+            return None;
+        }
+        let (exported, rest) = match trimmed.strip_prefix("export ") {
+            Some(after) => (true, after.trim_start()),
+            None => (false, trimmed),
+        };
+        let eq = rest.find('=')?;
+        let key = rest[..eq].trim_end();
+        if !is_valid_key(key) {
+            return None;
+        }
+        let after_eq = rest[eq + 1..].trim_start();
+
+        // Line breaks inside a multi-line quoted value are kept as written (`\n` or `\r\n`).
+        let (value, comment, extra) = parse_value(after_eq, first_eol, &lines[start + 1..])?;
+        let last = lines[start + extra];
+        let (_, eol) = split_eol(last);
+        let raw: String = lines[start..=start + extra].concat();
+        Some((
+            Entry {
+                key: key.to_string(),
+                value,
+                line: start + 1,
+                exported,
+                indent: indent.to_string(),
+                comment,
+                eol: eol.to_string(),
+                raw: Some(raw),
+            },
+            extra + 1,
+        ))
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Entry(e) => Some(e),
+            Item::Raw(_) => None,
+        })
+    }
+
+    /// Value of the first occurrence of `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries().find(|e| e.key == key).map(|e| e.value.as_str())
+    }
+
+    /// Keys that appear more than once, in order of first appearance.
+    pub fn duplicate_keys(&self) -> Vec<String> {
+        let mut seen: Vec<&str> = Vec::new();
+        let mut dups: Vec<String> = Vec::new();
+        for e in self.entries() {
+            if seen.contains(&e.key.as_str()) {
+                if !dups.contains(&e.key) {
+                    dups.push(e.key.clone());
+                }
+            } else {
+                seen.push(&e.key);
+            }
+        }
+        dups
+    }
+
+    /// Update the first occurrence of `key` in place (keeping indentation, `export` and any
+    /// inline comment), or append it at the end.
+    pub fn set(&mut self, key: &str, value: &str) {
+        for item in self.items.iter_mut() {
+            if let Item::Entry(e) = item {
+                if e.key == key {
+                    e.value = value.to_string();
+                    e.raw = None;
+                    return;
+                }
+            }
+        }
+
+        let eol = self.line_ending();
+        if let Some(Item::Raw(last)) | Some(Item::Entry(Entry { raw: Some(last), .. })) = self.items.last_mut() {
+            if !last.ends_with('\n') {
+                last.push_str(eol);
+            }
+        }
+        if let Some(Item::Entry(last)) = self.items.last_mut() {
+            if last.eol.is_empty() {
+                last.eol = eol.to_string();
+            }
+        }
+        let line = self.items.iter().map(item_line_count).sum::<usize>() + 1;
+        self.items.push(Item::Entry(Entry {
+            key: key.to_string(),
+            value: value.to_string(),
+            line,
+            exported: false,
+            indent: String::new(),
+            comment: None,
+            eol: eol.to_string(),
+            raw: None,
+        }));
+    }
+
+    /// Remove every occurrence of `key`. Returns whether anything was removed.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let before = self.items.len();
+        self.items
+            .retain(|item| !matches!(item, Item::Entry(e) if e.key == key));
+        self.items.len() != before
+    }
+
+    /// The file's dominant line ending, for newly written lines.
+    fn line_ending(&self) -> &'static str {
+        let text = self.to_string();
+        if text.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    }
+}
+
+fn item_line_count(item: &Item) -> usize {
+    let text = match item {
+        Item::Raw(raw) => raw.clone(),
+        Item::Entry(e) => e.render(),
+    };
+    text.matches('\n').count().max(1)
+}
+
+impl fmt::Display for EnvFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            match item {
+                Item::Raw(raw) => f.write_str(raw)?,
+                Item::Entry(e) => f.write_str(&e.render())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn parses_exports_quotes_comments_and_multiline_values() {
+        let text = "# header\n\
+                    export A=1\n\
+                    B = \"two words\" # note\n\
+                    C='it''s'\n\
+                    D=plain#not-a-comment # comment\n\
+                    G= # just a comment\n\
+                    E=\"line1\nline2\"\n\
+                    F=\"esc\\n\\\"q\\\"\"\n\
+                    not a valid line\n\
+                    A=ignored\n";
+        let env = EnvFile::parse(text);
+        assert_eq!(env.get("A"), Some("1"));
+        assert_eq!(env.get("B"), Some("two words"));
+        assert_eq!(env.get("C"), None);
+        assert_eq!(env.get("D"), Some("plain#not-a-comment"));
+        assert_eq!(env.get("G"), Some(""));
+        assert_eq!(env.get("E"), Some("line1\nline2"));
+        assert_eq!(env.get("F"), Some("esc\n\"q\""));
+        assert_eq!(env.duplicate_keys(), vec!["A".to_string()]);
+        assert_eq!(env.entries().find(|e| e.key == "F").map(|e| e.line), Some(9));
+        assert_eq!(env.to_string(), text);
+        let mut env = env;
+        env.set("G", "set");
+        assert_eq!(EnvFile::parse(&env.to_string()).get("G"), Some("set"));
+    }
+
+    #[test]
+    fn set_and_remove_leave_other_lines_untouched() {
+        let text = "# keep\r\nexport A=1 # first\r\nB='x'\r\nA=2";
+        let mut env = EnvFile::parse(text);
+        env.set("A", "new value");
+        env.set("C", "3");
+        assert_eq!(
+            env.to_string(),
+            "# keep\r\nexport A=\"new value\" # first\r\nB='x'\r\nA=2\r\nC=3\r\n"
+        );
+        assert!(env.remove("A"));
+        assert_eq!(env.to_string(), "# keep\r\nB='x'\r\nC=3\r\n");
+        assert!(!env.remove("A"));
+    }
+
+    fn key_strategy() -> impl Strategy<Value = String> {
+        "[A-Za-z_][A-Za-z0-9_]{0,12}"
+    }
+
+    proptest! {
+        #[test]
+        fn parse_of_written_values_round_trips(
+            pairs in proptest::collection::btree_map(key_strategy(), any::<String>(), 0..8)
+        ) {
+            let mut env = EnvFile::default();
+            for (k, v) in &pairs {
+                env.set(k, v);
+            }
+            let reparsed = EnvFile::parse(&env.to_string());
+            for (k, v) in &pairs {
+                prop_assert_eq!(reparsed.get(k), Some(v.as_str()));
+            }
+            prop_assert_eq!(reparsed.entries().count(), pairs.len());
+        }
+
+        #[test]
+        fn unmodified_files_are_written_back_verbatim(text in "[ -~\t\r\n]{0,200}") {
+            prop_assert_eq!(EnvFile::parse(&text).to_string(), text);
+        }
+
+        #[test]
+        fn updating_one_key_keeps_every_other_value(
+            pairs in proptest::collection::btree_map(key_strategy(), any::<String>(), 1..8),
+            new_value in any::<String>()
+        ) {
+            let mut env = EnvFile::default();
+            for (k, v) in &pairs {
+                env.set(k, v);
+            }
+            let target = pairs.keys().next().unwrap().clone();
+            let mut edited = EnvFile::parse(&env.to_string());
+            edited.set(&target, &new_value);
+            let reparsed = EnvFile::parse(&edited.to_string());
+            for (k, v) in &pairs {
+                let expected = if *k == target { new_value.as_str() } else { v.as_str() };
+                prop_assert_eq!(reparsed.get(k), Some(expected));
+            }
+        }
+    }
+}
@@ -1,4 +1,10 @@
 mod commands;
+mod config_bundle;
+mod dotenv;
+mod persist;
+mod pricing;
+mod settings;
+mod spend_ledger;
 mod auth_pb;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -12,33 +18,69 @@ pub fn run() {
             .build(),
         )?;
       }
+      // Clean up daemons orphaned by a previous crash or force quit.
+      std::thread::spawn(commands::reap_orphan_daemons);
+      commands::install_engine_panic_hook();
+      commands::spawn_engine_monitor(app.handle().clone());
+      commands::spawn_settings_watcher(app.handle().clone());
       Ok(())
     })
     .on_window_event(|_window, event| {
       if matches!(event, tauri::WindowEvent::CloseRequested { .. }) {
-        commands::engine_kill();
+        commands::engine_kill_all();
       }
     })
     .invoke_handler(tauri::generate_handler![
       commands::engine_start,
       commands::engine_kill,
+      commands::engine_list,
+      commands::engine_logs,
+      commands::engine_request,
+      commands::engine_stats,
+      commands::diagnostics_collect,
       commands::fs_list_tree,
       commands::fs_read_text,
       commands::env_find,
+      commands::env_resolve,
       commands::env_ensure_file,
       commands::env_ensure_file_at_path,
       commands::budget_get_status,
       commands::budget_set_main_limit,
+      commands::budget_set_agent_limit,
+      commands::budget_set_provider_limit,
+      commands::budget_record_spend,
+      commands::spend_ledger_query,
+      commands::spend_ledger_entries,
+      commands::pricing_get,
+      commands::pricing_set_model,
       commands::env_upsert_key,
       commands::env_upsert_key_at_path,
+      commands::env_list_keys,
+      commands::env_delete_key,
       commands::env_global_path,
       commands::snailer_env_file_get,
       commands::snailer_env_file_set,
+      commands::settings_get,
+      commands::settings_patch,
+      commands::config_export,
+      commands::config_import,
+      commands::env_profile_list,
+      commands::env_profile_create,
+      commands::env_profile_switch,
+      commands::env_profile_delete,
       commands::snailer_cli_ensure_installed,
       commands::snailer_cli_status,
+      commands::snailer_cli_check_update,
+      commands::snailer_cli_update,
+      commands::snailer_cli_rollback,
       commands::auth_addr_get,
       commands::auth_addr_set,
       commands::auth_addr_resolve,
+      commands::offline_bundle_get,
+      commands::offline_bundle_set,
+      commands::npm_registry_get,
+      commands::npm_registry_set,
+      commands::npm_registry_token_set,
       commands::attachment_save_image,
       commands::attachment_save_image_from_path,
       commands::gitignore_ensure_line,
@@ -49,6 +91,7 @@ pub fn run() {
       commands::auth_logout,
       commands::auth_check,
       commands::openai_login_status,
+      commands::provider_key_validate,
       commands::openai_login_start,
       commands::openai_login_logout,
       commands::open_external_url,
@@ -81,6 +124,12 @@ pub fn run() {
       commands::gh_pr_view_comments,
       commands::gh_issue_view_comments,
     ])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    .run(|_app, event| {
+      // Covers menu quit / Cmd-Q, which never send CloseRequested.
+      if matches!(event, tauri::RunEvent::ExitRequested { .. } | tauri::RunEvent::Exit) {
+        commands::engine_kill_all();
+      }
+    });
 }
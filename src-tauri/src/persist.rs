@@ -0,0 +1,271 @@
+//! Crash- and race-safe persistence for small state files (`~/.snailer/*.json`, `.env`).
+//!
+//! Every write:
+//! - holds an advisory lock for the whole read-modify-write, so concurrent commands (or a
+//!   second app instance) can't lose each other's updates;
+//! - writes a temp file in the same directory, fsyncs it and renames it over the target, so
+//!   readers never see a truncated file;
+//! - optionally keeps the previous good contents as `<file>.bak`, which readers fall back to
+//!   when the main file is missing or unreadable.
+//!
+//! Lock files live under the per-user `~/.snailer/locks` (keyed by a hash of the target path)
+//! rather than next to the target, so project directories don't collect stray `.lock` files.
+//! A shared temp dir would be owned by whichever user created it first.
+
+use fs4::fs_std::FileExt;
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Whether to keep `<file>.bak`, and what counts as "good" contents worth backing up or
+/// recovering from.
+#[derive(Clone, Copy)]
+pub struct Policy {
+    pub backup: bool,
+    pub is_valid: fn(&str) -> bool,
+}
+
+impl Policy {
+    /// JSON state files: back up and only accept contents that parse as JSON.
+    pub const JSON: Policy = Policy {
+        backup: true,
+        is_valid: is_json,
+    };
+    /// Plain text with a backup (e.g. `~/.snailer/.env`).
+    pub const TEXT: Policy = Policy {
+        backup: true,
+        is_valid: any_text,
+    };
+    /// Plain text without a backup, for files outside `~/.snailer` such as a project `.env`,
+    /// where a secret-bearing `.env.bak` could end up committed.
+    pub const TEXT_NO_BACKUP: Policy = Policy {
+        backup: false,
+        is_valid: any_text,
+    };
+}
+
+fn is_json(text: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(text).is_ok()
+}
+
+fn any_text(_: &str) -> bool {
+    true
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let abs = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+    let digest = Sha256::digest(abs.to_string_lossy().as_bytes());
+    let key: String = digest.iter().take(12).map(|b| format!("{:02x}", b)).collect();
+    crate::commands::snailer_home_dir().join("locks").join(format!("{}.lock", key))
+}
+
+/// Create the lock directory readable by its owner only.
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)
+}
+
+/// Exclusive advisory lock on `path`, released when the guard is dropped.
+pub struct FileLock {
+    _file: File,
+}
+
+pub fn lock(path: &Path) -> Result<FileLock, String> {
+    let lock_path = lock_path(path);
+    if let Some(parent) = lock_path.parent() {
+        create_private_dir(parent).map_err(|e| format!("mkdir failed: {}", e))?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| format!("lock open failed: {}", e))?;
+    file.lock_exclusive().map_err(|e| format!("lock failed: {}", e))?;
+    Ok(FileLock { _file: file })
+}
+
+/// Read `path`, falling back to `<file>.bak` when the main file is unreadable or invalid.
+/// A missing main file means "no state" (it was deleted on purpose), not corruption.
+pub fn read(path: &Path, policy: Policy) -> Option<String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) if (policy.is_valid)(&text) => return Some(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        _ => {}
+    }
+    if !policy.backup {
+        return None;
+    }
+    std::fs::read_to_string(backup_path(path))
+        .ok()
+        .filter(|text| (policy.is_valid)(text))
+}
+
+/// Read `path` and deserialize it, falling back to the backup if the main file doesn't parse.
+pub fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Option<T> {
+    let text = read(path, Policy::JSON)?;
+    if let Ok(v) = serde_json::from_str(&text) {
+        return Some(v);
+    }
+    std::fs::read_to_string(backup_path(path))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+}
+
+/// Replace the contents of `path` atomically.
+pub fn write(path: &Path, contents: &str, policy: Policy) -> Result<(), String> {
+    let _lock = lock(path)?;
+    write_locked(path, contents, policy)
+}
+
+/// Locked read-modify-write. `f` receives the current contents (recovered from the backup if
+/// needed; `None` if there's nothing usable) and returns the new contents.
+pub fn update<F>(path: &Path, policy: Policy, f: F) -> Result<(), String>
+where
+    F: FnOnce(Option<String>) -> Result<String, String>,
+{
+    let _lock = lock(path)?;
+    let next = f(read(path, policy))?;
+    write_locked(path, &next, policy)
+}
+
+/// Like [`update`], but `f` returns `None` to leave the file untouched. Returns whether it
+/// was rewritten.
+pub fn update_if<F>(path: &Path, policy: Policy, f: F) -> Result<bool, String>
+where
+    F: FnOnce(Option<String>) -> Result<Option<String>, String>,
+{
+    let _lock = lock(path)?;
+    match f(read(path, policy))? {
+        Some(next) => write_locked(path, &next, policy).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+fn write_locked(path: &Path, contents: &str, policy: Policy) -> Result<(), String> {
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    std::fs::create_dir_all(&parent).map_err(|e| format!("mkdir failed: {}", e))?;
+
+    let existing = std::fs::metadata(path).ok();
+    if policy.backup && existing.is_some() {
+        if let Ok(current) = std::fs::read_to_string(path) {
+            if (policy.is_valid)(&current) {
+                replace_file(&backup_path(path), current.as_bytes(), existing.as_ref())?;
+            }
+        }
+    }
+    replace_file(path, contents.as_bytes(), existing.as_ref())?;
+    sync_dir(&parent);
+    Ok(())
+}
+
+/// Temp file + fsync + rename. Keeps the permissions of the file being replaced.
+fn replace_file(path: &Path, bytes: &[u8], existing: Option<&std::fs::Metadata>) -> Result<(), String> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".tmp-{}-{}", std::process::id(), uuid::Uuid::new_v4().simple()));
+    let tmp = path.with_file_name(tmp_name);
+
+    let result = (|| {
+        let mut file = File::create(&tmp).map_err(|e| format!("write failed: {}", e))?;
+        if let Some(meta) = existing {
+            let _ = file.set_permissions(meta.permissions());
+        }
+        file.write_all(bytes).map_err(|e| format!("write failed: {}", e))?;
+        file.sync_all().map_err(|e| format!("fsync failed: {}", e))?;
+        drop(file);
+        std::fs::rename(&tmp, path).map_err(|e| format!("rename failed: {}", e))
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Persist the rename itself. Directories can't be opened for fsync on Windows; skip there.
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(d) = File::open(dir) {
+        let _ = d.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrent_updates_are_not_lost_and_backup_recovers_truncation() {
+        let dir = std::env::temp_dir().join(format!("snailer-persist-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("state.json");
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for j in 0..10 {
+                        update(&path, Policy::JSON, |current| {
+                            let mut obj: serde_json::Map<String, serde_json::Value> =
+                                current.map(|t| serde_json::from_str(&t).unwrap()).unwrap_or_default();
+                            obj.insert(format!("k{}_{}", i, j), serde_json::json!(true));
+                            Ok(serde_json::Value::Object(obj).to_string())
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+        let obj: serde_json::Map<String, serde_json::Value> = read_json(&path).unwrap();
+        assert_eq!(obj.len(), 80);
+
+        // A torn write leaves invalid JSON behind; readers recover the last good copy.
+        std::fs::write(&path, "{\"k0_0\": tr").unwrap();
+        let recovered: serde_json::Map<String, serde_json::Value> = read_json(&path).unwrap();
+        assert_eq!(recovered.len(), 79);
+
+        // The truncated file is never promoted to the backup.
+        write(&path, "{}", Policy::JSON).unwrap();
+        assert!(is_json(&std::fs::read_to_string(backup_path(&path)).unwrap()));
+
+        let leftovers = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().contains(".tmp-"))
+            .count();
+        assert_eq!(leftovers, 0);
+
+        let lock_dir = lock_path(&path).parent().unwrap().to_path_buf();
+        assert!(lock_dir.starts_with(crate::commands::snailer_home_dir()));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let fresh = dir.join("locks").join("nested");
+            create_private_dir(&fresh).unwrap();
+            assert_eq!(std::fs::metadata(&fresh).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
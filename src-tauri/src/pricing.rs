@@ -0,0 +1,254 @@
+//! Per-model prices for budget math.
+//!
+//! A built-in table covers the models the GUI calls directly; `~/.snailer/pricing.json`
+//! (`{ "models": { "<model>": { "input": .., "output": .. } } }`) adds models or replaces
+//! built-in entries wholesale. Lookups return `None` for models in neither, so callers can flag
+//! them instead of costing them at zero.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Token prices are USD per 1M tokens; `web_search_call` is USD per call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    /// Defaults to `input` when the provider has no cache discount.
+    #[serde(default)]
+    pub cached_input: Option<f64>,
+    #[serde(default)]
+    pub web_search_call: Option<f64>,
+}
+
+impl ModelPrice {
+    const fn tokens(input: f64, output: f64, cached_input: Option<f64>) -> ModelPrice {
+        ModelPrice {
+            input,
+            output,
+            cached_input,
+            web_search_call: None,
+        }
+    }
+
+    const fn with_web_search(mut self, per_call: f64) -> ModelPrice {
+        self.web_search_call = Some(per_call);
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let fields = [
+            ("input", Some(self.input)),
+            ("output", Some(self.output)),
+            ("cachedInput", self.cached_input),
+            ("webSearchCall", self.web_search_call),
+        ];
+        for (name, value) in fields {
+            if value.is_some_and(|v| !v.is_finite() || v < 0.0) {
+                return Err(format!("{} must be a non-negative number", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Part of `input_tokens` served from cache.
+    pub cached_input_tokens: u64,
+    pub web_search_calls: u64,
+}
+
+pub fn cost(price: &ModelPrice, usage: Usage) -> f64 {
+    let cached = usage.cached_input_tokens.min(usage.input_tokens);
+    let uncached = usage.input_tokens - cached;
+    let per_token = |tokens: u64, per_million: f64| tokens as f64 / 1_000_000.0 * per_million;
+    per_token(uncached, price.input)
+        + per_token(cached, price.cached_input.unwrap_or(price.input))
+        + per_token(usage.output_tokens, price.output)
+        + usage.web_search_calls as f64 * price.web_search_call.unwrap_or(0.0)
+}
+
+pub fn defaults() -> BTreeMap<String, ModelPrice> {
+    [
+        // xAI
+        ("grok-4", ModelPrice::tokens(3.0, 15.0, Some(0.75))),
+        ("grok-4-1-fast", ModelPrice::tokens(1.0, 5.0, None).with_web_search(0.005)),
+        // OpenAI
+        ("gpt-4o", ModelPrice::tokens(2.5, 10.0, Some(1.25))),
+        ("gpt-4o-mini", ModelPrice::tokens(0.15, 0.6, Some(0.075))),
+        ("gpt-5.2", ModelPrice::tokens(1.75, 14.0, Some(0.175)).with_web_search(0.01)),
+        ("gpt-5.3-codex", ModelPrice::tokens(1.75, 14.0, Some(0.175))),
+        // Anthropic
+        ("claude-sonnet-4-6", ModelPrice::tokens(3.0, 15.0, Some(0.3))),
+        ("claude-sonnet-4-20250514", ModelPrice::tokens(3.0, 15.0, Some(0.3))),
+        ("claude-opus-4-20250514", ModelPrice::tokens(15.0, 75.0, Some(1.5))),
+        ("claude-opus-4-6", ModelPrice::tokens(5.0, 25.0, Some(0.5))),
+        // Moonshot/Kimi
+        ("kimi-k2-turbo-preview", ModelPrice::tokens(0.5, 2.0, None).with_web_search(0.005)),
+        ("kimi-k2.5", ModelPrice::tokens(0.5, 2.0, None).with_web_search(0.005)),
+        ("moonshot-v1-128k", ModelPrice::tokens(0.8, 3.2, None)),
+    ]
+    .into_iter()
+    .map(|(model, price)| (model.to_string(), price))
+    .collect()
+}
+
+/// Lowercase and drop a provider prefix (`openai/gpt-5.2`, `anthropic:claude-opus-4-6`).
+/// `-` is not a separator: it's part of model names such as `moonshot-v1-128k`.
+pub fn canonical_model(model: &str) -> String {
+    let m = model.trim().to_ascii_lowercase();
+    for provider in ["anthropic", "openai", "xai", "moonshot", "google"] {
+        for sep in ['/', ':'] {
+            if let Some(rest) = m.strip_prefix(provider).and_then(|r| r.strip_prefix(sep)) {
+                return rest.to_string();
+            }
+        }
+    }
+    m
+}
+
+/// Exact match first, then the longest table entry the model extends with a date suffix
+/// (a dated snapshot such as `claude-opus-4-6-20260201` or `gpt-4o-2024-08-06`). Other
+/// suffixes name sibling models with their own prices (`grok-4-fast`), so they don't match.
+pub fn lookup<'a>(table: &'a BTreeMap<String, ModelPrice>, model: &str) -> Option<(&'a str, &'a ModelPrice)> {
+    let m = canonical_model(model);
+    if let Some((k, v)) = table.get_key_value(&m) {
+        return Some((k.as_str(), v));
+    }
+    table
+        .iter()
+        .filter(|(k, _)| m.strip_prefix(k.as_str()).is_some_and(is_date_suffix))
+        .max_by_key(|(k, _)| k.len())
+        .map(|(k, v)| (k.as_str(), v))
+}
+
+/// `-YYYYMMDD` or `-YYYY-MM-DD`.
+fn is_date_suffix(rest: &str) -> bool {
+    let digits = |s: &str, n: usize| s.len() == n && s.bytes().all(|b| b.is_ascii_digit());
+    let Some(date) = rest.strip_prefix('-') else {
+        return false;
+    };
+    if digits(date, 8) {
+        return true;
+    }
+    let parts: Vec<&str> = date.split('-').collect();
+    parts.len() == 3 && digits(parts[0], 4) && digits(parts[1], 2) && digits(parts[2], 2)
+}
+
+/// User overrides from `pricing.json`; a missing file means none, a malformed one is an error.
+pub fn load_overrides(path: &Path) -> Result<BTreeMap<String, ModelPrice>, String> {
+    let Some(text) = crate::persist::read(path, crate::persist::Policy::JSON) else {
+        if path.exists() {
+            return Err(format!("{} is not valid JSON; fix or delete it", path.display()));
+        }
+        return Ok(BTreeMap::new());
+    };
+    parse_overrides(&text).map_err(|e| format!("{} is malformed ({}); fix or delete it", path.display(), e))
+}
+
+fn parse_overrides(text: &str) -> Result<BTreeMap<String, ModelPrice>, String> {
+    let doc: Map<String, Value> = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let Some(models) = doc.get("models") else {
+        return Ok(BTreeMap::new());
+    };
+    let models: BTreeMap<String, ModelPrice> =
+        serde_json::from_value(models.clone()).map_err(|e| format!("invalid models: {}", e))?;
+    let mut out = BTreeMap::new();
+    for (model, price) in models {
+        price.validate().map_err(|e| format!("{}: {}", model, e))?;
+        out.insert(canonical_model(&model), price);
+    }
+    Ok(out)
+}
+
+/// Built-in prices with the user's overrides applied.
+pub fn merged(overrides: &BTreeMap<String, ModelPrice>) -> BTreeMap<String, ModelPrice> {
+    let mut table = defaults();
+    table.extend(overrides.iter().map(|(k, v)| (k.clone(), v.clone())));
+    table
+}
+
+/// Set (`Some`) or remove (`None`) one override, keeping any other keys in the file.
+pub fn set_override(path: &Path, model: &str, price: Option<&ModelPrice>) -> Result<(), String> {
+    if let Some(price) = price {
+        price.validate()?;
+    }
+    let model = canonical_model(model);
+    if model.is_empty() {
+        return Err("model is empty".to_string());
+    }
+    crate::persist::update(path, crate::persist::Policy::JSON, |current| {
+        let mut doc: Map<String, Value> = match current {
+            Some(text) => {
+                parse_overrides(&text).map_err(|e| format!("{} is malformed ({}); fix or delete it", path.display(), e))?;
+                serde_json::from_str(&text).map_err(|e| e.to_string())?
+            }
+            None => Map::new(),
+        };
+        let models = doc
+            .entry("models")
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+            .ok_or_else(|| "models must be an object".to_string())?;
+        // Entries may have been written with a provider prefix; replace any spelling.
+        models.retain(|k, _| canonical_model(k) != model);
+        if let Some(price) = price {
+            let value = serde_json::to_value(price).map_err(|e| format!("serialize failed: {}", e))?;
+            models.insert(model.clone(), value);
+        }
+        serde_json::to_string_pretty(&Value::Object(doc)).map_err(|e| format!("serialize failed: {}", e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups_normalize_names_and_overrides_replace_defaults() {
+        let table = defaults();
+        assert_eq!(lookup(&table, "OpenAI/gpt-4o-mini").unwrap().0, "gpt-4o-mini");
+        assert_eq!(lookup(&table, "claude-opus-4-6-20260201").unwrap().0, "claude-opus-4-6");
+        assert!(lookup(&table, "gpt-4").is_none());
+        assert!(lookup(&table, "grok-4x").is_none());
+        assert_eq!(lookup(&table, "gpt-4o-2024-08-06").unwrap().0, "gpt-4o");
+        assert!(lookup(&table, "grok-4-fast").is_none());
+        assert!(lookup(&table, "gpt-4o-audio-preview").is_none());
+        assert_eq!(lookup(&table, "moonshot-v1-128k").unwrap().0, "moonshot-v1-128k");
+        assert_eq!(lookup(&table, "moonshot/moonshot-v1-128k").unwrap().0, "moonshot-v1-128k");
+        assert_eq!(canonical_model("Anthropic:claude-opus-4-6"), "claude-opus-4-6");
+
+        let usage = Usage {
+            input_tokens: 2_000_000,
+            output_tokens: 1_000_000,
+            cached_input_tokens: 1_000_000,
+            web_search_calls: 10,
+        };
+        let (_, gpt) = lookup(&table, "gpt-5.2").unwrap();
+        assert!((cost(gpt, usage) - (1.75 + 0.175 + 14.0 + 0.1)).abs() < 1e-9);
+
+        let dir = std::env::temp_dir().join(format!("snailer-pricing-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("pricing.json");
+        assert!(load_overrides(&path).unwrap().is_empty());
+        let custom = ModelPrice::tokens(9.0, 9.0, None);
+        set_override(&path, "xai/grok-4", Some(&custom)).unwrap();
+        set_override(&path, "my-local-model", Some(&ModelPrice::tokens(0.0, 0.0, None))).unwrap();
+        let table = merged(&load_overrides(&path).unwrap());
+        assert_eq!(table["grok-4"], custom);
+        assert!(lookup(&table, "my-local-model").is_some());
+        set_override(&path, "grok-4", None).unwrap();
+        set_override(&path, "moonshot-v1-128k", Some(&custom)).unwrap();
+        assert!(load_overrides(&path).unwrap().contains_key("moonshot-v1-128k"));
+        assert_eq!(merged(&load_overrides(&path).unwrap())["grok-4"], defaults()["grok-4"]);
+
+        assert!(set_override(&path, "x", Some(&ModelPrice::tokens(-1.0, 0.0, None))).is_err());
+        std::fs::write(&path, r#"{"models":{"x":{"input":"cheap"}}}"#).unwrap();
+        assert!(load_overrides(&path).unwrap_err().contains("malformed"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
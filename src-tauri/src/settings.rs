@@ -0,0 +1,249 @@
+//! Typed, versioned `~/.snailer/gui_settings.json`.
+//!
+//! Files are migrated forward on load (`schemaVersion` missing = version 0). Unknown keys are
+//! kept as-is so settings written by other tools or newer builds survive a round trip. A file
+//! that can't be parsed is an error, never silently replaced with defaults; the `.bak` kept by
+//! [`crate::persist`] is used to recover from a torn write.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Optional resource limits for managed daemons (`engineLimits`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineLimits {
+    pub max_rss_mb: Option<u64>,
+    pub max_cpu_percent: Option<f32>,
+    pub max_open_fds: Option<usize>,
+    #[serde(default)]
+    pub restart_on_breach: bool,
+}
+
+impl EngineLimits {
+    pub fn is_empty(&self) -> bool {
+        self.max_rss_mb.is_none() && self.max_cpu_percent.is_none() && self.max_open_fds.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GuiSettings {
+    pub schema_version: u32,
+    /// `SNAILER_ENV_FILE` passed to daemons launched by the GUI.
+    pub snailer_env_file: Option<String>,
+    pub auth_addr: Option<String>,
+    /// Env profile under `~/.snailer/profiles/`; `None` means the shared `~/.snailer/.env`.
+    pub active_env_profile: Option<String>,
+    pub offline_bundle_dir: Option<String>,
+    pub npm_registry: Option<String>,
+    pub npm_scope_registry: Option<String>,
+    pub engine_limits: Option<EngineLimits>,
+    /// Send `env.reload` to running daemons when the active env file changes.
+    pub reload_daemon_on_env_change: bool,
+    /// Keys this build doesn't know about, preserved verbatim.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for GuiSettings {
+    fn default() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            snailer_env_file: None,
+            auth_addr: None,
+            active_env_profile: None,
+            offline_bundle_dir: None,
+            npm_registry: None,
+            npm_scope_registry: None,
+            engine_limits: None,
+            reload_daemon_on_env_change: false,
+            extra: Map::new(),
+        }
+    }
+}
+
+impl GuiSettings {
+    /// Blank strings mean "unset" (older builds and hand edits write `""`).
+    fn drop_blank_strings(&mut self) {
+        for field in [
+            &mut self.snailer_env_file,
+            &mut self.auth_addr,
+            &mut self.active_env_profile,
+            &mut self.offline_bundle_dir,
+            &mut self.npm_registry,
+            &mut self.npm_scope_registry,
+        ] {
+            if field.as_deref().is_some_and(|s| s.trim().is_empty()) {
+                *field = None;
+            }
+        }
+    }
+}
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+/// `MIGRATIONS[n]` upgrades a version-`n` document to version `n + 1`.
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1];
+
+/// v0 (unversioned): keys were written one at a time, blanks and `null` both meant unset.
+fn migrate_v0_to_v1(obj: &mut Map<String, Value>) -> Result<(), String> {
+    obj.retain(|_, v| !v.is_null() && v.as_str().map(|s| !s.trim().is_empty()).unwrap_or(true));
+    Ok(())
+}
+
+fn migrate(obj: &mut Map<String, Value>) -> Result<(), String> {
+    let version = match obj.get("schemaVersion") {
+        None => 0,
+        Some(v) => v
+            .as_u64()
+            .ok_or_else(|| format!("schemaVersion must be a non-negative integer, got {}", v))?,
+    };
+    if version > SCHEMA_VERSION as u64 {
+        return Err(format!(
+            "schemaVersion {} is newer than this app supports ({}); update Snailer",
+            version, SCHEMA_VERSION
+        ));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(obj)?;
+    }
+    obj.insert("schemaVersion".to_string(), Value::from(SCHEMA_VERSION));
+    Ok(())
+}
+
+/// Parse and migrate a settings document.
+pub fn parse(text: &str) -> Result<GuiSettings, String> {
+    let value: Value = serde_json::from_str(text)
+        .map_err(|e| format!("invalid JSON at line {} column {}: {}", e.line(), e.column(), e))?;
+    let Value::Object(mut obj) = value else {
+        return Err("expected a JSON object".to_string());
+    };
+    migrate(&mut obj)?;
+    let mut settings: GuiSettings =
+        serde_json::from_value(Value::Object(obj)).map_err(|e| format!("invalid setting: {}", e))?;
+    settings.drop_blank_strings();
+    Ok(settings)
+}
+
+fn malformed(path: &Path, err: &str) -> String {
+    format!("{} is malformed ({}); fix or delete it", path.display(), err)
+}
+
+/// Load settings; a missing file yields defaults, a malformed one (with no usable backup) is an error.
+pub fn load(path: &Path) -> Result<GuiSettings, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(GuiSettings::default()),
+        Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+    };
+    parse(&text).or_else(|err| {
+        std::fs::read_to_string(crate::persist::backup_path(path))
+            .ok()
+            .and_then(|bak| parse(&bak).ok())
+            .ok_or_else(|| malformed(path, &err))
+    })
+}
+
+/// Locked load-modify-save. Refuses to overwrite a malformed file.
+pub fn update<F>(path: &Path, f: F) -> Result<GuiSettings, String>
+where
+    F: FnOnce(&mut GuiSettings) -> Result<(), String>,
+{
+    let mut saved = None;
+    crate::persist::update(path, crate::persist::Policy::JSON, |current| {
+        let mut settings = match current {
+            Some(text) => parse(&text).map_err(|e| malformed(path, &e))?,
+            None if path.exists() => {
+                let err = std::fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|t| parse(&t).map(|_| ()))
+                    .err()
+                    .unwrap_or_default();
+                return Err(malformed(path, &err));
+            }
+            None => GuiSettings::default(),
+        };
+        f(&mut settings)?;
+        settings.schema_version = SCHEMA_VERSION;
+        settings.drop_blank_strings();
+        let text = serde_json::to_string_pretty(&settings).map_err(|e| format!("serialize failed: {}", e))?;
+        saved = Some(settings);
+        Ok(text)
+    })?;
+    saved.ok_or_else(|| "settings update did not run".to_string())
+}
+
+/// Apply a partial update (`{ "authAddr": "...", "npmRegistry": null }`). `null` resets a key to
+/// its default; unknown keys and `schemaVersion` are rejected; types are checked by serde.
+pub fn apply_patch(settings: &GuiSettings, patch: &Value) -> Result<GuiSettings, String> {
+    let Value::Object(patch) = patch else {
+        return Err("settings patch must be a JSON object".to_string());
+    };
+    let known = match serde_json::to_value(GuiSettings::default()) {
+        Ok(Value::Object(obj)) => obj,
+        _ => Map::new(),
+    };
+    let mut obj = match serde_json::to_value(settings) {
+        Ok(Value::Object(obj)) => obj,
+        _ => return Err("failed to serialize current settings".to_string()),
+    };
+    for (key, value) in patch {
+        if key == "schemaVersion" {
+            return Err("schemaVersion cannot be patched".to_string());
+        }
+        if !known.contains_key(key) {
+            return Err(format!("unknown setting: {}", key));
+        }
+        if value.is_null() {
+            obj.remove(key);
+        } else {
+            obj.insert(key.clone(), value.clone());
+        }
+    }
+    let mut next: GuiSettings =
+        serde_json::from_value(Value::Object(obj)).map_err(|e| format!("invalid settings patch: {}", e))?;
+    next.drop_blank_strings();
+    Ok(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_files_migrate_and_malformed_files_error() {
+        let legacy = r#"{"snailerEnvFile":"/home/u/.snailer/.env","authAddr":"","offlineBundleDir":null,"theme":"dark"}"#;
+        let s = parse(legacy).unwrap();
+        assert_eq!(s.schema_version, SCHEMA_VERSION);
+        assert_eq!(s.snailer_env_file.as_deref(), Some("/home/u/.snailer/.env"));
+        assert_eq!(s.auth_addr, None);
+        assert_eq!(s.extra.get("theme"), Some(&serde_json::json!("dark")));
+
+        assert!(parse("{\"authAddr\": ").unwrap_err().contains("line 1"));
+        assert!(parse("{\"schemaVersion\": 99}").unwrap_err().contains("newer"));
+        assert!(parse("{\"engineLimits\": {\"maxRssMb\": \"lots\"}}").is_err());
+
+        let patched = apply_patch(&s, &serde_json::json!({ "npmRegistry": "https://r.example/", "snailerEnvFile": null })).unwrap();
+        assert_eq!(patched.npm_registry.as_deref(), Some("https://r.example/"));
+        assert_eq!(patched.snailer_env_file, None);
+        assert!(apply_patch(&s, &serde_json::json!({ "npmRegistyr": "x" })).unwrap_err().contains("unknown setting"));
+        assert!(apply_patch(&s, &serde_json::json!({ "reloadDaemonOnEnvChange": "yes" })).is_err());
+
+        let dir = std::env::temp_dir().join(format!("snailer-settings-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("gui_settings.json");
+        assert_eq!(load(&path).unwrap(), GuiSettings::default());
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(load(&path).unwrap_err().contains("malformed"));
+        assert!(update(&path, |s| {
+            s.auth_addr = Some("x".to_string());
+            Ok(())
+        })
+        .is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{ not json");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,300 @@
+//! Append-only spend ledger (`~/.snailer/spend_ledger.jsonl`).
+//!
+//! One JSON object per line, one line per billed call. Lines are only ever appended (under the
+//! same advisory lock [`crate::persist`] uses), so history survives the monthly reset of
+//! `budget_state.json`. A torn last line from a crash is skipped on read rather than failing
+//! the whole query.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerEntry {
+    /// RFC 3339, UTC.
+    pub ts: String,
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    pub provider: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cached_input_tokens: u64,
+    #[serde(default)]
+    pub web_search_calls: u64,
+    pub cost_usd: f64,
+    /// The model had no price, so `cost_usd` is a placeholder zero rather than a real cost.
+    #[serde(default)]
+    pub unpriced: bool,
+    #[serde(default)]
+    pub request_id: Option<String>,
+}
+
+impl LedgerEntry {
+    fn date(&self) -> Option<chrono::NaiveDate> {
+        chrono::DateTime::parse_from_rfc3339(&self.ts)
+            .ok()
+            .map(|t| t.with_timezone(&chrono::Utc).date_naive())
+    }
+}
+
+/// How far back [`append`] looks for a duplicate `request_id`. Retries land within seconds of
+/// the original, so the tail of the ledger is enough and appends stay cheap as it grows.
+const DEDUPE_TAIL_BYTES: u64 = 256 * 1024;
+
+/// Append `entry`, unless its `request_id` is already among the recent entries (a retried
+/// call); returns whether a line was written.
+pub fn append(path: &Path, entry: &LedgerEntry) -> Result<bool, String> {
+    let mut line = serde_json::to_string(entry).map_err(|e| format!("serialize failed: {}", e))?;
+    line.push('\n');
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("mkdir failed: {}", e))?;
+    }
+    let _lock = crate::persist::lock(path)?;
+    if let Some(id) = entry.request_id.as_deref() {
+        if tail_has_request_id(path, id, DEDUPE_TAIL_BYTES)? {
+            return Ok(false);
+        }
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("ledger open failed: {}", e))?;
+    // Terminate a torn last line so it doesn't swallow this entry.
+    if ends_mid_line(&mut file) {
+        line.insert(0, '\n');
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = file.set_permissions(std::fs::Permissions::from_mode(0o600));
+    }
+    file.write_all(line.as_bytes()).map_err(|e| format!("ledger write failed: {}", e))?;
+    file.sync_data().map_err(|e| format!("fsync failed: {}", e))?;
+    Ok(true)
+}
+
+/// Whether any entry in the last `max_bytes` of the ledger has `request_id`.
+fn tail_has_request_id(path: &Path, request_id: &str, max_bytes: u64) -> Result<bool, String> {
+    use std::io::{Read, Seek, SeekFrom};
+    let mut file = match std::fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(format!("ledger read failed: {}", e)),
+    };
+    let len = file.metadata().map_err(|e| format!("ledger read failed: {}", e))?.len();
+    // Read one byte before the window so a line starting exactly at its edge isn't mistaken
+    // for a fragment.
+    let start = len.saturating_sub(max_bytes).saturating_sub(1);
+    file.seek(SeekFrom::Start(start))
+        .map_err(|e| format!("ledger read failed: {}", e))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)
+        .map_err(|e| format!("ledger read failed: {}", e))?;
+    if len > max_bytes {
+        // Starting mid-file: drop everything up to the first line break.
+        let cut = tail.iter().position(|b| *b == b'\n').map_or(tail.len(), |i| i + 1);
+        tail.drain(..cut);
+    }
+    Ok(String::from_utf8_lossy(&tail)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<LedgerEntry>(line).ok())
+        .any(|e| e.request_id.as_deref() == Some(request_id)))
+}
+
+fn ends_mid_line(file: &mut std::fs::File) -> bool {
+    use std::io::{Read, Seek, SeekFrom};
+    let mut last = [0u8; 1];
+    file.seek(SeekFrom::End(-1)).is_ok() && file.read_exact(&mut last).is_ok() && last[0] != b'\n'
+}
+
+/// All readable entries in file order; a missing ledger is empty.
+pub fn read(path: &Path) -> Result<Vec<LedgerEntry>, String> {
+    let file = match std::fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("ledger read failed: {}", e)),
+    };
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("ledger read failed: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<LedgerEntry>(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => log::warn!("skipping unreadable spend ledger line: {}", e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Inclusive UTC date range plus exact-match filters; `None` matches everything.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub from: Option<chrono::NaiveDate>,
+    pub to: Option<chrono::NaiveDate>,
+    pub agent_id: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+}
+
+impl Filter {
+    pub fn matches(&self, entry: &LedgerEntry) -> bool {
+        let date = entry.date();
+        if self.from.is_some_and(|from| date.map_or(true, |d| d < from))
+            || self.to.is_some_and(|to| date.map_or(true, |d| d > to))
+        {
+            return false;
+        }
+        let eq = |want: &Option<String>, have: Option<&str>| want.as_deref().map_or(true, |w| have == Some(w));
+        eq(&self.agent_id, entry.agent_id.as_deref())
+            && eq(&self.provider, Some(entry.provider.as_str()))
+            && eq(&self.model, entry.model.as_deref())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Day,
+    Month,
+    Agent,
+    Provider,
+    Model,
+}
+
+impl GroupBy {
+    pub fn parse(s: &str) -> Result<GroupBy, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "day" => Ok(GroupBy::Day),
+            "month" => Ok(GroupBy::Month),
+            "agent" => Ok(GroupBy::Agent),
+            "provider" => Ok(GroupBy::Provider),
+            "model" => Ok(GroupBy::Model),
+            other => Err(format!(
+                "unknown groupBy '{}': use day, month, agent, provider or model",
+                other
+            )),
+        }
+    }
+
+    fn key(self, entry: &LedgerEntry) -> String {
+        let or_unknown = |v: Option<&str>| v.unwrap_or("unknown").to_string();
+        match self {
+            GroupBy::Day => or_unknown(entry.date().map(|d| d.format("%Y-%m-%d").to_string()).as_deref()),
+            GroupBy::Month => or_unknown(entry.date().map(|d| d.format("%Y-%m").to_string()).as_deref()),
+            GroupBy::Agent => or_unknown(entry.agent_id.as_deref()),
+            GroupBy::Provider => entry.provider.clone(),
+            GroupBy::Model => or_unknown(entry.model.as_deref()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Aggregate {
+    pub key: String,
+    pub calls: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cached_input_tokens: u64,
+    pub web_search_calls: u64,
+    pub cost_usd: f64,
+    /// Calls whose model had no price; `cost_usd` undercounts by these.
+    pub unpriced_calls: u64,
+}
+
+/// Totals per group, sorted by key (chronological for day/month).
+pub fn aggregate<'a>(entries: impl IntoIterator<Item = &'a LedgerEntry>, group_by: GroupBy) -> Vec<Aggregate> {
+    let mut groups: BTreeMap<String, Aggregate> = BTreeMap::new();
+    for entry in entries {
+        let key = group_by.key(entry);
+        let agg = groups.entry(key.clone()).or_insert_with(|| Aggregate {
+            key,
+            ..Default::default()
+        });
+        agg.calls += 1;
+        agg.input_tokens += entry.input_tokens;
+        agg.output_tokens += entry.output_tokens;
+        agg.cached_input_tokens += entry.cached_input_tokens;
+        agg.web_search_calls += entry.web_search_calls;
+        agg.cost_usd += entry.cost_usd;
+        agg.unpriced_calls += u64::from(entry.unpriced);
+    }
+    groups.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(ts: &str, agent: &str, model: &str, cost: f64) -> LedgerEntry {
+        LedgerEntry {
+            ts: ts.to_string(),
+            agent_id: Some(agent.to_string()),
+            provider: "xai".to_string(),
+            model: Some(model.to_string()),
+            input_tokens: 100,
+            output_tokens: 10,
+            cached_input_tokens: 0,
+            web_search_calls: 0,
+            cost_usd: cost,
+            unpriced: false,
+            request_id: None,
+        }
+    }
+
+    #[test]
+    fn appended_entries_survive_torn_lines_and_aggregate_by_group() {
+        let dir = std::env::temp_dir().join(format!("snailer-ledger-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("spend_ledger.jsonl");
+        assert!(read(&path).unwrap().is_empty());
+        append(&path, &entry("2026-09-30T23:59:00Z", "pm", "grok-4", 0.5)).unwrap();
+        append(&path, &entry("2026-10-01T01:00:00+02:00", "pm", "gpt-5.2", 0.25)).unwrap();
+        append(&path, &entry("2026-10-02T10:00:00Z", "coder", "gpt-5.2", 1.0)).unwrap();
+        // Crash mid-append.
+        let mut f = OpenOptions::new().append(true).open(&path).unwrap();
+        f.write_all(b"{\"ts\":\"2026-10-0").unwrap();
+        drop(f);
+
+        let mut retried = entry("2026-10-02T11:00:00Z", "coder", "grok-4", 0.0);
+        retried.request_id = Some("req-1".to_string());
+        let before = std::fs::metadata(&path).unwrap().len();
+        assert!(append(&path, &retried).unwrap());
+        assert!(!append(&path, &retried).unwrap());
+        // Only the tail is searched for duplicates.
+        let line_len = std::fs::metadata(&path).unwrap().len() - before;
+        assert!(tail_has_request_id(&path, "req-1", line_len).unwrap());
+        assert!(!tail_has_request_id(&path, "req-1", line_len - 2).unwrap());
+
+        let entries = read(&path).unwrap();
+        assert_eq!(entries.len(), 4);
+        let months = aggregate(&entries, GroupBy::Month);
+        assert_eq!(months.iter().map(|a| (a.key.as_str(), a.calls)).collect::<Vec<_>>(), vec![("2026-09", 2), ("2026-10", 2)]);
+        let models = aggregate(&entries, GroupBy::Model);
+        assert_eq!(models[0].key, "gpt-5.2");
+        assert_eq!(models[0].cost_usd, 1.25);
+        assert_eq!(models[0].input_tokens, 200);
+
+        let filter = Filter {
+            from: chrono::NaiveDate::from_ymd_opt(2026, 10, 1),
+            agent_id: Some("coder".to_string()),
+            ..Default::default()
+        };
+        let days = aggregate(entries.iter().filter(|e| filter.matches(e)), GroupBy::Day);
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].calls, 2);
+        assert_eq!(days[0].key, "2026-10-02");
+        assert!(GroupBy::parse("week").is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
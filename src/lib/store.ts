@@ -320,6 +320,7 @@ export interface LlmCompletionResponse {
   input_tokens: number
   output_tokens: number
   cached_input_tokens?: number
+  web_search_calls?: number
 }
 
 // Per-agent token usage tracking
@@ -4469,7 +4470,7 @@ If your current broadcast is PR/Issue-related, handle actionable items first. Ot
 
                   // Check credential errors — only trigger for genuinely missing keys
                   // (e.g. "XAI_API_KEY not found in ~/.snailer/.env"), NOT for generic auth/token errors from APIs
-                  const needsInput = /not found in ~\/.snailer\/.env|Failed to read ~\/.snailer\/.env/i.test(errMsg)
+                  const needsInput = /not found in ~\/.snailer\/|Failed to read ~\/.snailer\//i.test(errMsg)
                   if (needsInput) {
                     const inputValue = await get().agentRequestInput({
                       agentId,